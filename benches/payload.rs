@@ -0,0 +1,110 @@
+//! compares copying a text payload for every connection of a room with sharing a single buffer between them.
+//!
+//! run it with `cargo bench --bench payload`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use actix_web::{body::MessageBody, test::TestRequest, web, FromRequest};
+use actix_wsb::{ByteString, Room};
+
+const CONNECTIONS: usize = 1000;
+const ROUNDS: usize = 20;
+const PAYLOAD_SIZE: usize = 50 * 1024;
+
+/// counts the allocated bytes, so the benchmark can show how much each way allocates.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// creates a websocket session from a test request and drains it's response in the background, like a client that reads everything.
+async fn session() -> actix_ws::Session {
+    let (req, mut payload) = TestRequest::get().insert_header(("upgrade", "websocket"))
+                                               .insert_header(("connection", "upgrade"))
+                                               .insert_header(("sec-websocket-version", "13"))
+                                               .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+                                               .to_http_parts();
+
+    let body = web::Payload::from_request(&req, &mut payload).await.unwrap();
+    let (response, session, stream) = actix_ws::handle(&req, body).unwrap();
+
+    actix_web::rt::spawn(async move {
+        let _stream = stream;
+        let mut body = response.into_body();
+
+        while let Some(Ok(_)) = futures_util::future::poll_fn(|cx| Pin::new(&mut body).poll_next(cx)).await {}
+    });
+
+    session
+}
+
+async fn room() -> Room {
+    let mut room = Room::create("bench".to_string());
+
+    room.set_queue_capacity(ROUNDS + 1);
+
+    for id in 0..CONNECTIONS {
+        room.add_connection(&id.to_string(), session().await).unwrap();
+    }
+
+    room
+}
+
+/// waits until the writer tasks sent everything, so the next case starts with empty queues.
+async fn drain(room: &Room) {
+    while room.connectors.values().any(|connection| connection.queued() > 0) {
+        actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+fn report(name: &str, elapsed: Duration, allocated: usize) {
+    println!("{:<28} {:>10.2?} per broadcast {:>10.2} MiB allocated per broadcast", name, elapsed / ROUNDS as u32, allocated as f64 / ROUNDS as f64 / 1024.0 / 1024.0);
+}
+
+#[actix_web::main]
+async fn main() {
+    let payload = format!("{{\"data\":\"{}\"}}", "x".repeat(PAYLOAD_SIZE));
+    let mut room = room().await;
+
+    println!("{} connections, {} KiB payload, {} rounds", CONNECTIONS, PAYLOAD_SIZE / 1024, ROUNDS);
+
+    // the way the broadcasts worked before: the payload is cloned for every connection.
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        for connection in room.connectors.values_mut() {
+            let _ = connection.send(payload.clone()).await;
+        }
+    }
+
+    report("copy per connection", start.elapsed(), ALLOCATED.load(Ordering::Relaxed) - allocated);
+    drain(&room).await;
+
+    // a single buffer is shared between all the connections.
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        let _ = room.broadcast(ByteString::from(payload.clone())).await;
+    }
+
+    report("shared ByteString", start.elapsed(), ALLOCATED.load(Ordering::Relaxed) - allocated);
+    drain(&room).await;
+}
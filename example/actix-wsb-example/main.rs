@@ -1,14 +1,15 @@
 use actix_web::{rt::spawn, web::{get, Data, Payload, Query}, App, HttpRequest, HttpResponse, HttpServer, Responder};
 
-use actix_wsb::Broadcaster;
+use actix_wsb::{BroadcasterHandle, HeartbeatConfig};
 use askama::Template;
-use serde;
-use std::sync::{Arc, RwLock};
 use actix_ws::{Item, Message};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let rooms = Broadcaster::new();
+    let rooms = BroadcasterHandle::new();
+
+    // pings every connection every 10 seconds, closes and removes the ones that missed 3 pings in a row:
+    rooms.heartbeat(HeartbeatConfig::default());
 
     let server = HttpServer::new(move || {
         App::new()
@@ -45,13 +46,16 @@ pub async fn chat_controller(inputs: Query<ChatInputs>) -> impl Responder {
     HttpResponse::Ok().content_type("text/html").body(template)
 }
 
-pub async fn websocket_controller(req: HttpRequest, body: Payload, broadcaster: Data<Arc<RwLock<Broadcaster>>>, query: Query<WebsocketInput>) -> actix_web::Result<impl Responder> {
+pub async fn websocket_controller(req: HttpRequest, body: Payload, broadcaster: Data<BroadcasterHandle>, query: Query<WebsocketInput>) -> actix_web::Result<impl Responder> {
     let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
 
     let id = query.id.as_ref().unwrap().to_string();
     let room_id = query.room.as_ref().unwrap().to_string();
 
-    let get_broadcaster = Broadcaster::handle(&broadcaster, &room_id, &id, session);
+    broadcaster.handle(&room_id, &id, session).map_err(actix_web::error::ErrorInternalServerError)?;
+
+    // the handle is only an `Arc` under the hood, so cloning it is cheap:
+    let get_broadcaster = broadcaster.get_ref().clone();
 
     // ".each_room_immut()" example
     get_broadcaster.read().unwrap().each_room_immut(|room| println!("Hello to room {}!", room.id));
@@ -61,7 +65,7 @@ pub async fn websocket_controller(req: HttpRequest, body: Payload, broadcaster:
     
     get_broadcaster.read().unwrap().each_room(|room| {
         for _ in room.connectors.iter() {
-            num = num + 1
+            num += 1
         }
     });
     
@@ -69,50 +73,46 @@ pub async fn websocket_controller(req: HttpRequest, body: Payload, broadcaster:
         while let Some(Ok(msg)) = msg_stream.recv().await {
             match msg {
                 Message::Text(msg) => {
-                    let mut write_broadcaster = get_broadcaster.write().unwrap();
-
-                    write_broadcaster.room(&room_id).broadcast(msg.to_string()).await;
+                    // the handle copies the sessions of the room and releases the lock before sending:
+                    if let Ok(report) = get_broadcaster.broadcast(&room_id, msg).await {
+                        for (id, error) in report.failed {
+                            println!("message couldn't be sent to {}: {}", id, error);
+                        }
+                    }
                 },
                  Message::Close(reason) => {
                     // warning, that closes and removes all the connections but not removes the room: 
-                    //let _ = get_broadcaster.write().unwrap().room(room_id.clone()).close(reason).await;
+                    //let _ = get_broadcaster.close(&room_id, reason).await;
                     
                     // if you want to remove a room with removing all the connections, use this instead:
-                    // let _ = get_broadcaster.write().unwrap().remove_room(room_id.clone()).await;
+                    // let _ = get_broadcaster.remove_room(&room_id).await;
 
                     // you can conditionally close connections:
-                    //let _ = get_broadcaster.write().unwrap().room(room_id.clone()).close_if(reason, |conn| conn.id == query.id.clone().unwrap()).await;
+                    //let _ = get_broadcaster.close_if(&room_id, reason, |conn| conn.id == query.id.clone().unwrap()).await;
                     
-                    // or, use the new api:
+                    // or, close only this connection:
 
-                    let _ = get_broadcaster.write().unwrap().room(&room_id).close_conn(reason, &id).await;
+                    let _ = get_broadcaster.close_conn(&room_id, reason, &id).await;
                     break;
                  },
                  Message::Pong(bytes) => {
-                    let mut write_broadcaster = get_broadcaster.write().unwrap();
-
-                    write_broadcaster.room(&room_id).ping(bytes.to_vec()).await;
+                    // tells the heartbeat supervisor that this connection is still alive:
+                    let _ = get_broadcaster.record_pong(&id, &bytes);
                  },
                  Message::Ping(bytes) => {
-                    let mut write_broadcaster = get_broadcaster.write().unwrap();
-
-                    write_broadcaster.room(&room_id).pong(bytes.to_vec()).await;
+                    let _ = get_broadcaster.pong_if(&room_id, bytes, |conn| conn.id == id).await;
                  },
                  Message::Continuation(item) => {
-                    let mut write_broadcaster = get_broadcaster.write().unwrap();
-
-                    let room = write_broadcaster.room(&room_id);
-
                     let msg = format!(r"hello, your continuation message: {:#?}", item);
                     
                     let start = Item::FirstBinary(msg.into());
-                    let _ = room.continuation(start).await;
+                    let _ = get_broadcaster.continuation(&room_id, start).await;
 
                     let cont_cont = Item::Continue(r"continue".into());
-                    let _ = room.continuation(cont_cont).await;
+                    let _ = get_broadcaster.continuation(&room_id, cont_cont).await;
 
                     let last = Item::Last(r"end".into());
-                    let _ = room.continuation(last);
+                    let _ = get_broadcaster.continuation(&room_id, last).await;
 
                  }
                  _ => ()
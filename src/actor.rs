@@ -0,0 +1,612 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+use actix_web::HttpRequest;
+use actix_ws::{CloseReason, Message, Session};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::admission::JoinHooks;
+use crate::event::Listeners;
+use crate::index::Index;
+use crate::shard::Settings;
+use crate::surface::send_methods;
+use crate::{close_memberships, close_released, Closing, Connection, DeliveryReport, DuplicatePolicy, Error, Event, FanOut, Handshake, Id, IdGenerator, JoinRequest, Metadata, Room, SlowConsumerPolicy};
+
+/// default count of the commands that can wait in the mailbox of a room.
+const DEFAULT_MAILBOX: usize = 256;
+
+type Condition<S, C> = Box<dyn Fn(&Connection<S, C>) -> bool + Send>;
+
+type Task<S, R, C> = Box<dyn FnOnce(&mut Room<S, R, C>) + Send>;
+
+/// the commands that the task of a room processes one by one, in the order they're sent.
+enum Command<S, R, C> {
+    Join { id: C, session: Session, metadata: Metadata, state: S, reply: oneshot::Sender<Result<(), Error>> },
+    Leave { id: C, reply: oneshot::Sender<Result<(), Error>> },
+    SendAll { message: Message, reply: oneshot::Sender<DeliveryReport<C>> },
+    Send { message: Message, condition: Condition<S, C>, reply: oneshot::Sender<DeliveryReport<C>> },
+    Close { reason: Option<CloseReason>, condition: Condition<S, C>, reply: oneshot::Sender<DeliveryReport<C>> },
+    CloseConn { reason: Option<CloseReason>, id: C, reply: oneshot::Sender<Result<(), Error>> },
+    Run(Task<S, R, C>),
+    Shutdown { reply: oneshot::Sender<DeliveryReport<C>> },
+    ShutdownIfEmpty { reply: oneshot::Sender<bool> }
+}
+
+/// a handle of a room that runs as it's own task. The task owns the room and it's connectors and processes the commands that sent from the handles one by one, so the messages that sent to a room from concurrent senders reach every connection in the same order, without any lock. It's cheap to clone, the task stops when every handle of the room is dropped or the room is removed.
+///
+/// ```rust,ignore
+///
+/// let room = RoomHandle::spawn(Room::create("1".to_string()));
+///
+/// room.join(&id, session).await?;
+///
+/// let report = room.broadcast("hello".to_string()).await?;
+///
+/// ```
+pub struct RoomHandle<S = (), R = String, C = String> {
+    id: R,
+    commands: mpsc::Sender<Command<S, R, C>>
+}
+
+impl<S, R: Clone, C> Clone for RoomHandle<S, R, C> {
+    fn clone(&self) -> Self {
+        Self { id: self.id.clone(), commands: self.commands.clone() }
+    }
+}
+
+impl<S: Clone + 'static, R: Id, C: Id> RoomHandle<S, R, C> {
+    /// spawns the task of given room on the current actix runtime, so it has to be called inside of it.
+    pub fn spawn(room: Room<S, R, C>) -> Self {
+        Self::with_mailbox(room, DEFAULT_MAILBOX)
+    }
+
+    /// spawns the task of given room with a mailbox that holds given count of commands at most, zero is treated as one. When the mailbox is full, senders wait until the room catches up.
+    pub fn with_mailbox(room: Room<S, R, C>, capacity: usize) -> Self {
+        let (commands, receiver) = mpsc::channel(capacity.max(1));
+        let id = room.id.clone();
+
+        actix_web::rt::spawn(run(room, receiver));
+
+        Self { id, commands }
+    }
+
+    /// returns the id of the room.
+    pub fn id(&self) -> &R {
+        &self.id
+    }
+
+    /// adds the session to the room as a connection with given id like `Room::add_connection()`, so the duplicate policy of the room applies and `Error::Duplicate` is returned if the session is rejected. It returns after the room processed it, so the messages that sent after that reach the connection.
+    pub async fn join<K>(&self, id: &K, session: Session) -> Result<(), Error> where S: Default, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.join_with(id, session, Metadata::default()).await
+    }
+
+    /// same as `.join()`, but the connection has given metadata.
+    pub async fn join_with<K>(&self, id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Join { id: id.to_owned(), session, metadata, state: S::default(), reply }, receiver).await?
+    }
+
+    /// same as `.join()`, but the connection has given state.
+    pub async fn join_with_state<K>(&self, id: &K, session: Session, state: S) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Join { id: id.to_owned(), session, metadata: Metadata::default(), state, reply }, receiver).await?
+    }
+
+    /// removes the connection with given id from the room without closing it, returns `Error::ConnectionNotFound` if there is no connection with that id.
+    pub async fn leave<K>(&self, id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Leave { id: id.to_owned(), reply }, receiver).await?
+    }
+
+    /// runs given function with the room inside of it's task and returns the result of it. Use it for reading or changing the room, the other commands wait until it returns.
+    ///
+    /// ```rust,ignore
+    ///
+    /// let count = room.with(|room| room.connectors.len()).await?;
+    ///
+    /// room.with(|room| room.set_concurrency(Some(32))).await?;
+    ///
+    /// ```
+    pub async fn with<F, T>(&self, f: F) -> Result<T, Error> where F: FnOnce(&mut Room<S, R, C>) -> T + Send + 'static, T: Send + 'static {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Run(Box::new(move |room| { let _ = reply.send(f(room)); })), receiver).await
+    }
+
+    send_methods! {
+        receiver: [],
+        room: [],
+        generics: [],
+        bounds: [],
+        condition: [+ Send + 'static],
+        output: Result<DeliveryReport<C>, Error>,
+        close: [async await] Result<DeliveryReport<C>, Error>,
+    }
+
+    /// closes the connection with given id and removes it from the room.
+    pub async fn close_conn<K>(&self, reason: Option<CloseReason>, id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::CloseConn { reason, id: id.to_owned(), reply }, receiver).await?
+    }
+
+    /// closes all the connections of the room and stops it's task after the commands that sent before. The other handles of the room get `Error::RoomNotFound` after that.
+    pub async fn shutdown(&self) -> Result<DeliveryReport<C>, Error> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Shutdown { reply }, receiver).await
+    }
+
+    /// stops the task of the room if it has no connection, checking it and stopping in the same command so a connection that joins meanwhile can't be lost. Returns true if the room is stopped.
+    pub(crate) async fn shutdown_if_empty(&self) -> Result<bool, Error> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::ShutdownIfEmpty { reply }, receiver).await
+    }
+
+    /// returns true if the task of the room is stopped.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.commands.is_closed()
+    }
+
+    /// returns true if both handles belong to the same task.
+    pub(crate) fn same(&self, other: &Self) -> bool {
+        self.commands.same_channel(&other.commands)
+    }
+
+    async fn send_all(&self, message: Message) -> Result<DeliveryReport<C>, Error> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::SendAll { message, reply }, receiver).await
+    }
+
+    async fn dispatch<F>(&self, message: Message, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool + Send + 'static {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Send { message, condition: Box::new(condition), reply }, receiver).await
+    }
+
+    async fn close_where<F>(&self, reason: Option<CloseReason>, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool + Send + 'static {
+        let (reply, receiver) = oneshot::channel();
+
+        self.request(Command::Close { reason, condition: Box::new(condition), reply }, receiver).await
+    }
+
+    /// sends the command to the mailbox of the room and waits for it's reply. Returns `Error::RoomNotFound` if the task of the room is stopped.
+    async fn request<T>(&self, command: Command<S, R, C>, receiver: oneshot::Receiver<T>) -> Result<T, Error> {
+        self.commands.send(command).await.map_err(|_| Error::RoomNotFound(self.id.to_string()))?;
+
+        receiver.await.map_err(|_| Error::RoomNotFound(self.id.to_string()))
+    }
+}
+
+/// the task of a room.
+async fn run<S: Clone, R: Id, C: Id>(mut room: Room<S, R, C>, mut commands: mpsc::Receiver<Command<S, R, C>>) {
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Join { id, session, metadata, state, reply } => {
+                let _ = reply.send(room.insert_connection(&id, session, metadata, state, None));
+            },
+            Command::Leave { id, reply } => {
+                let _ = reply.send(room.remove_connection::<C>(&id));
+            },
+            Command::SendAll { message, reply } => {
+                let _ = reply.send(room.send_all(message).await);
+            },
+            Command::Send { message, condition, reply } => {
+                let _ = reply.send(room.dispatch(message, condition).await);
+            },
+            // the connections are removed in order, but their close frames are sent in another task, so a client that doesn't read them can't stop the mailbox.
+            Command::Close { reason, condition, reply } => {
+                reply_later(room.close_where(reason, condition), reply);
+            },
+            Command::CloseConn { reason, id, reply } => {
+                reply_later(room.close_conn(reason, &id), reply);
+            },
+            Command::Run(f) => f(&mut room),
+            Command::Shutdown { reply } => {
+                reply_later(room.close(None), reply);
+
+                return;
+            },
+            Command::ShutdownIfEmpty { reply } => {
+                room.reap();
+
+                let empty = room.connectors.is_empty();
+                let _ = reply.send(empty);
+
+                if empty {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// awaits the closing in it's own task and sends the result of it as the reply.
+fn reply_later<T: Send + 'static>(closing: Closing<T>, reply: oneshot::Sender<T>) {
+    actix_web::rt::spawn(async move {
+        let _ = reply.send(closing.await);
+    });
+}
+
+/// a lightweight router of rooms that run as their own task, the room-as-actor alternative of `Broadcaster`. It only holds the handles of the rooms by their id, so it's lock is held only for looking up or adding a room, never while sending. It's cheap to clone.
+///
+/// ```rust,ignore
+///
+/// let broadcaster = RoomRouter::new();
+///
+/// broadcaster.handle(&room_id, &id, session).await?;
+///
+/// let report = broadcaster.room(&room_id)?.broadcast(msg.to_string()).await?;
+///
+/// ```
+pub struct RoomRouter<S = (), R = String, C = String> {
+    inner: Arc<Router<S, R, C>>
+}
+
+struct Router<S, R, C> {
+    rooms: RwLock<HashMap<R, RoomHandle<S, R, C>>>,
+    events: Listeners<R, C>,
+    join_hooks: JoinHooks<S, R, C>,
+    index: Index<R, C>,
+    settings: RwLock<Settings>
+}
+
+impl<S, R, C> Clone for RoomRouter<S, R, C> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<S, R, C> Default for RoomRouter<S, R, C> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Router {
+                rooms: RwLock::default(),
+                events: Listeners::default(),
+                join_hooks: JoinHooks::default(),
+                index: Index::default(),
+                settings: RwLock::default()
+            })
+        }
+    }
+}
+
+impl RoomRouter {
+    /// creates a router without any room.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Clone + Send + 'static, R: Id, C: Id> RoomRouter<S, R, C> {
+    /// creates a router without any room whose connections have a state of type `S`, it's the equivalent of `Broadcaster::typed()`.
+    pub fn typed() -> Self {
+        Self::default()
+    }
+
+    /// adds the session to the room with given id as a connection with given id, spawns the room if it's not exist. It's the equivalent of `Broadcaster::handle()`.
+    pub async fn handle<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), S::default(), None).await
+    }
+
+    /// same as `.handle()`, but the connection has given metadata.
+    pub async fn handle_with<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, metadata, S::default(), None).await
+    }
+
+    /// same as `.handle()`, but the connection has given state.
+    pub async fn handle_with_state<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), state, None).await
+    }
+
+    /// same as `.handle()`, but the join hooks get the handshake request of the connection. It's the equivalent of `Broadcaster::handle_request()`.
+    pub async fn handle_request<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), S::default(), Some(&Handshake::from(request))).await
+    }
+
+    /// same as `.handle_request()`, but the connection has given metadata. It's the equivalent of `Broadcaster::handle_request_with()`.
+    pub async fn handle_request_with<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, metadata, S::default(), Some(&Handshake::from(request))).await
+    }
+
+    /// same as `.handle_request()`, but the connection has given state. It's the equivalent of `Broadcaster::handle_request_with_state()`.
+    pub async fn handle_request_with_state<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), state, Some(&Handshake::from(request))).await
+    }
+
+    /// returns the handle of the room with given id, spawns the room if it's not exist or it's task is stopped by `.remove_empty_rooms()`.
+    pub fn handle_room<Q>(&self, id: &Q) -> Result<RoomHandle<S, R, C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        if let Some(room) = self.check_room(id).filter(|room| !room.is_stopped()) {
+            return Ok(room);
+        }
+
+        let mut rooms = self.inner.rooms.write()?;
+
+        if let Some(room) = rooms.get(id).filter(|room| !room.is_stopped()) {
+            return Ok(room.clone());
+        }
+
+        let room = RoomHandle::spawn(self.inner.settings.read()?.room(id.to_owned(), &self.inner.events, &self.inner.index));
+
+        rooms.insert(id.to_owned(), room.clone());
+        self.inner.events.emit(Event::RoomCreated { room: id.to_owned() });
+
+        Ok(room)
+    }
+
+    /// returns the handle of the room with given id, or `Error::RoomNotFound` if it's not exist.
+    pub fn room<Q>(&self, id: &Q) -> Result<RoomHandle<S, R, C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.inner.rooms.read()?.get(id).cloned().ok_or_else(|| Error::RoomNotFound(id.to_string()))
+    }
+
+    /// returns the handle of the room with given id if it's exist.
+    pub fn check_room<Q>(&self, id: &Q) -> Option<RoomHandle<S, R, C>> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.room(id).ok()
+    }
+
+    /// checks if a room with given id exist.
+    pub fn check<Q>(&self, id: &Q) -> bool where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.inner.rooms.read().map(|rooms| rooms.contains_key(id)).unwrap_or(false)
+    }
+
+    /// returns the id's of the rooms.
+    pub fn room_ids(&self) -> Result<Vec<R>, Error> {
+        Ok(self.inner.rooms.read()?.keys().cloned().collect())
+    }
+
+    /// registers a listener that will be called for every event that the rooms emit, like `.on_event()` method of `Broadcaster`. Listeners are called from the tasks of the rooms.
+    pub fn on_event<F>(&self, listener: F) where F: Fn(&Event<R, C>) + Send + Sync + 'static {
+        self.inner.events.push(listener);
+    }
+
+    /// registers a hook that decides if a connection can join a room with the `.handle()` or `.join()` methods, like `.on_join()` method of `Broadcaster`. Hooks are called from the tasks of the rooms.
+    pub fn on_join<F>(&self, hook: F) where F: Fn(&JoinRequest<S, R, C>) -> Result<(), CloseReason> + Send + Sync + 'static {
+        self.inner.join_hooks.push(hook);
+    }
+
+    /// sets how many connections of a room can be sent to at the same time, for every room that exist and will be created.
+    pub async fn set_concurrency(&self, limit: Option<usize>) -> Result<(), Error> {
+        self.inner.settings.write()?.concurrency = limit;
+
+        self.each_room(move |room| room.set_concurrency(limit)).await
+    }
+
+    /// sets how many messages the outbound queue of a connection can hold, for the rooms that exist and will be created. It affects the connections that will be added after that.
+    pub async fn set_queue_capacity(&self, capacity: usize) -> Result<(), Error> {
+        self.inner.settings.write()?.queue_capacity = capacity;
+
+        self.each_room(move |room| room.set_queue_capacity(capacity)).await
+    }
+
+    /// sets what happens when the outbound queue of a connection is full, for the rooms that exist and will be created. It affects the connections that will be added after that.
+    pub async fn set_slow_consumer_policy(&self, policy: SlowConsumerPolicy) -> Result<(), Error> {
+        self.inner.settings.write()?.slow_consumer_policy = policy.clone();
+
+        self.each_room(move |room| room.set_slow_consumer_policy(policy.clone())).await
+    }
+
+    /// sets what happens when a session is added with the id of a connection that's already in it's room, for the rooms that exist and will be created.
+    pub async fn set_duplicate_policy(&self, policy: DuplicatePolicy) -> Result<(), Error> {
+        self.inner.settings.write()?.duplicate_policy = policy.clone();
+
+        self.each_room(move |room| room.set_duplicate_policy(policy.clone())).await
+    }
+
+    /// enables or disables the presence of the rooms that exist and will be created, like `.set_presence()` method of `Broadcaster`.
+    pub async fn set_presence(&self, enabled: bool) -> Result<(), Error> {
+        self.inner.settings.write()?.presence = enabled;
+
+        self.each_room(move |room| room.set_presence(enabled)).await
+    }
+
+    /// sets how a room sends a message to it's connections, for the rooms that exist and will be created.
+    pub async fn set_fan_out(&self, fan_out: FanOut) -> Result<(), Error> {
+        self.inner.settings.write()?.fan_out = fan_out;
+
+        self.each_room(move |room| room.set_fan_out(fan_out)).await
+    }
+
+    /// sets how the router generates the id's of the connections that added with the `.handle_generated()` methods, default is `IdGenerator::UuidV4`.
+    pub fn set_id_generator(&self, generator: IdGenerator) -> Result<(), Error> {
+        self.inner.settings.write()?.ids.generator = generator;
+
+        Ok(())
+    }
+
+    /// removes the room with given id, closes all of it's connections and stops it's task.
+    pub async fn remove_room<Q>(&self, id: &Q) -> Result<DeliveryReport<C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let room = self.inner.rooms.write()?.remove(id).ok_or_else(|| Error::RoomNotFound(id.to_string()))?;
+        let report = room.shutdown().await;
+        self.inner.events.emit(Event::RoomRemoved { room: room.id });
+
+        report
+    }
+
+    /// removes the rooms that have no connection, stops their task and emits an `Event::RoomRemoved` for each of them. The dead connections are pruned first, like `.remove_empty_rooms()` method of `Broadcaster`. Each room checks that it's empty and stops in the same command, so a connection that joins meanwhile keeps it alive, and a `.handle()` that reaches a stopped room spawns it again.
+    pub async fn remove_empty_rooms(&self) -> Result<(), Error> {
+        let rooms: Vec<RoomHandle<S, R, C>> = self.inner.rooms.read()?.values().cloned().collect();
+
+        for room in rooms {
+            if room.shutdown_if_empty().await.unwrap_or(true) {
+                let mut rooms = self.inner.rooms.write()?;
+
+                // the room may be spawned again with the same id meanwhile.
+                if rooms.get(&room.id).is_some_and(|current| current.same(&room)) {
+                    rooms.remove(&room.id);
+                    drop(rooms);
+
+                    self.inner.events.emit(Event::RoomRemoved { room: room.id });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// removes the connection with given id from every room that it's in, without closing it. Returns `Error::ConnectionNotFound` if it's not in any room.
+    pub async fn remove_connection<K>(&self, id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(id) {
+            if let Some(room) = self.check_room(&room_id) {
+                found |= room.leave(id).await.is_ok();
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(id.to_string())) }
+    }
+
+    /// adds the connection with given id to the room with given id too, spawns the room if it's not exist. It's the equivalent of `.join()` method of `Broadcaster`.
+    pub async fn join<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let connection = self.find(conn_id).await.ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+        let hooks = self.inner.join_hooks.clone();
+
+        self.handle_room(room_id)?.with(move |room| {
+            room.welcome(&hooks, &connection)?;
+            room.join_copy(&connection);
+
+            Ok(())
+        }).await?
+    }
+
+    /// removes the connection with given id from the room with given id without closing it. It's the equivalent of `.leave()` method of `Broadcaster`.
+    pub async fn leave<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.room(room_id)?.leave(conn_id).await
+    }
+
+    /// returns the id's of the rooms that the connection with given id is in.
+    pub fn rooms_of<K>(&self, conn_id: &K) -> Vec<R> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.inner.index.rooms_of(conn_id)
+    }
+
+    /// closes the connection with given id and removes it from every room that it's in. It's the equivalent of `.disconnect()` method of `Broadcaster`.
+    pub async fn disconnect<K>(&self, reason: Option<CloseReason>, conn_id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let mut memberships = vec![];
+
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(room) = self.check_room(&room_id) {
+                let id = conn_id.to_owned();
+
+                if let Ok(Some(connection)) = room.with(move |room| room.evict::<C>(&id)).await {
+                    memberships.push(connection);
+                }
+            }
+        }
+
+        close_memberships(memberships, reason, conn_id).await
+    }
+
+    /// closes given session of the connection with given id, or the whole connection if that was it's last session. It's the equivalent of `.close_session()` method of `Broadcaster`.
+    pub async fn close_session<K>(&self, reason: Option<CloseReason>, conn_id: &K, session: Session) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        match self.find(conn_id).await {
+            Some(connection) if connection.outbound.release() => close_released(session, reason).await,
+            Some(_) => self.disconnect(reason, conn_id).await,
+            None => Err(Error::ConnectionNotFound(conn_id.to_string()))
+        }
+    }
+
+    /// adds the connection to the room with given id through the join hooks inside of the task of the room, so the hooks see the connections of it without a race. Spawns the room if it's not exist.
+    async fn admit<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata, state: S, handshake: Option<&Handshake>) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        loop {
+            let room = self.handle_room(room_id)?;
+            let hooks = self.inner.join_hooks.clone();
+            let id = conn_id.to_owned();
+            let (session, metadata, state, handshake) = (session.clone(), metadata.clone(), state.clone(), handshake.cloned());
+
+            match room.with(move |room| room.admit::<C>(&hooks, &id, session, metadata, state, handshake.as_ref())).await {
+                // the room is stopped by `.remove_empty_rooms()` before the join reached it, so it's spawned again.
+                Err(Error::RoomNotFound(_)) if room.is_stopped() => continue,
+                result => return result?
+            }
+        }
+    }
+
+    /// returns a copy of the connection with given id from one of it's rooms.
+    async fn find<K>(&self, conn_id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(room) = self.check_room(&room_id) {
+                let id = conn_id.to_owned();
+
+                if let Ok(Some(connection)) = room.with(move |room| room.check_connection::<C>(&id)).await {
+                    return Some(connection);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// changes the metadata of the connection with given id in every room that it's in, inside of the tasks of those rooms. Returns `Error::ConnectionNotFound` if it's not in any room.
+    pub async fn update_metadata<F, K>(&self, id: &K, f: F) -> Result<(), Error> where F: Fn(&mut Metadata) + Clone + Send + 'static, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(id) {
+            if let Some(room) = self.check_room(&room_id) {
+                let (id, f) = (id.to_owned(), f.clone());
+
+                found |= matches!(room.with(move |room| room.update_metadata::<_, C>(&id, f)).await, Ok(Ok(())));
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(id.to_string())) }
+    }
+
+    /// changes the state of the connection with given id in every room that it's in, inside of the tasks of those rooms. Returns `Error::ConnectionNotFound` if it's not in any room.
+    pub async fn update_state<F, K>(&self, id: &K, f: F) -> Result<(), Error> where F: Fn(&mut S) + Clone + Send + 'static, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(id) {
+            if let Some(room) = self.check_room(&room_id) {
+                let (id, f) = (id.to_owned(), f.clone());
+
+                found |= matches!(room.with(move |room| room.update_state::<_, C>(&id, f)).await, Ok(Ok(())));
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(id.to_string())) }
+    }
+
+    /// runs given function with every room inside of their task, one room at a time.
+    async fn each_room<F>(&self, f: F) -> Result<(), Error> where F: Fn(&mut Room<S, R, C>) + Clone + Send + 'static {
+        let rooms: Vec<RoomHandle<S, R, C>> = self.inner.rooms.read()?.values().cloned().collect();
+
+        for room in rooms {
+            // a room that's stopped meanwhile doesn't need the setting.
+            let _ = room.with(f.clone()).await;
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Clone + Send + 'static, R: Id> RoomRouter<S, R, String> {
+    /// same as `.handle()`, but the router generates a unique id for the connection and returns it. It's the equivalent of `Broadcaster::handle_generated()`.
+    pub async fn handle_generated<Q>(&self, room_id: &Q, session: Session) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        self.handle_generated_with_state(room_id, session, S::default()).await
+    }
+
+    /// same as `.handle_generated()`, but the connection has given metadata.
+    pub async fn handle_generated_with<Q>(&self, room_id: &Q, session: Session, metadata: Metadata) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let id = self.inner.settings.read()?.ids.next();
+
+        self.handle_with(room_id, &id, session, metadata).await?;
+
+        Ok(id)
+    }
+
+    /// same as `.handle_generated()`, but the connection has given state.
+    pub async fn handle_generated_with_state<Q>(&self, room_id: &Q, session: Session, state: S) -> Result<String, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let id = self.inner.settings.read()?.ids.next();
+
+        self.handle_with_state(room_id, &id, session, state).await?;
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use actix_web::http::header::HeaderMap;
+use actix_web::http::Uri;
+use actix_web::HttpRequest;
+use actix_ws::{CloseReason, Session};
+
+use crate::{Error, Metadata};
+
+/// a connection that's going to join a room with one of the `.handle()`, `.join()` or `.move_connection()` methods, which is given to the hooks that registered with `.on_join()`. `S`, `R` and `C` are the types of the state of the connection and the id's of the room and the connection.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct JoinRequest<'a, S = (), R = String, C = String> {
+    /// id of the room that the connection is going to join.
+    pub room: &'a R,
+    /// id of the connection.
+    pub connection: &'a C,
+    /// the metadata that the connection is added with, empty if it's added without one. Put what you read from the handshake request, like the user id or the password of the room, in it.
+    pub metadata: &'a Metadata,
+    /// the state that the connection is added with.
+    pub state: &'a S,
+    /// count of the connections that are in the room already.
+    pub members: usize,
+    /// the handshake request of the connection, if it's added with one of the `.handle_request()` methods. `.join()` and `.move_connection()` pass the one that the connection is added with. It's `None` for the other `.handle()` methods.
+    pub handshake: Option<&'a Handshake>
+}
+
+/// the parts of the handshake request of a connection that the join hooks can read, like the headers and the address of the client. It's created from the request with `Handshake::from(&req)`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Handshake {
+    /// the uri of the request, with the query string.
+    pub uri: Uri,
+    /// the headers of the request, like the cookies, the origin or the authorization.
+    pub headers: HeaderMap,
+    /// the address of the client, if it's known.
+    pub peer_addr: Option<SocketAddr>
+}
+
+impl From<&HttpRequest> for Handshake {
+    fn from(request: &HttpRequest) -> Self {
+        Self {
+            uri: request.uri().clone(),
+            headers: request.headers().clone(),
+            peer_addr: request.peer_addr()
+        }
+    }
+}
+
+type Hook<S, R, C> = Arc<dyn Fn(&JoinRequest<S, R, C>) -> Result<(), CloseReason> + Send + Sync>;
+
+/// the join hooks that shared between the broadcaster and it's clones, like `Listeners`.
+pub(crate) struct JoinHooks<S, R, C>(Arc<RwLock<Vec<Hook<S, R, C>>>>);
+
+impl<S, R, C> Clone for JoinHooks<S, R, C> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<S, R, C> Default for JoinHooks<S, R, C> {
+    fn default() -> Self {
+        Self(Arc::default())
+    }
+}
+
+impl<S, R, C> JoinHooks<S, R, C> {
+    pub(crate) fn push<F>(&self, hook: F) where F: Fn(&JoinRequest<S, R, C>) -> Result<(), CloseReason> + Send + Sync + 'static {
+        self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Arc::new(hook));
+    }
+
+    /// calls the hooks in the order they registered until one of them rejects the request. If one rejects, the session is closed with the reason that it returned and `Error::Rejected` is returned.
+    pub(crate) fn check(&self, request: &JoinRequest<S, R, C>, session: &Session) -> Result<(), Error> {
+        self.verify(request).map_err(|error| {
+            if let Error::Rejected(reason) = &error {
+                actix_web::rt::spawn(session.clone().close(Some(reason.clone())));
+            }
+
+            error
+        })
+    }
+
+    /// calls the hooks like `.check()` without closing the session, for the connections that are in another room already.
+    pub(crate) fn verify(&self, request: &JoinRequest<S, R, C>) -> Result<(), Error> {
+        let hooks = self.0.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+
+        for hook in hooks {
+            if let Err(reason) = hook(request) {
+                return Err(Error::Rejected(reason));
+            }
+        }
+
+        Ok(())
+    }
+}
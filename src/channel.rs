@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use actix_ws::Message;
+use tokio::sync::broadcast;
+
+use crate::queue::Subscription;
+use crate::{Connection, DeliveryReport, Event, Id, Room};
+
+/// how a room sends a message to it's connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FanOut {
+    /// the message is put in the outbound queue of every connection of the room one by one. This is the default.
+    #[default]
+    Direct,
+    /// the message is published once to a broadcast channel that holds given count of messages, and the writer task of every connection pulls it from there. It's for very large rooms. The connections that fall behind more than that lose the oldest messages and an `Event::Lagged` is emitted for them. Only the sends without a condition use the channel, `_if` and `_if_not` methods still send to every connection one by one.
+    ///
+    /// The writer task sends the messages of the queue first and pulls from the channel when the queue is empty, so the order between the messages of the channel and the queued ones, the `_if` and `_if_not` sends, the presence messages and the pings, isn't kept. A message that's sent with a condition after a broadcast can reach the client before it. Connections whose session is closed are pruned by the next send to the room and listed in the `pruned` of it's report.
+    Channel(usize)
+}
+
+/// the sender side of the channel of a room.
+pub(crate) type Channel = broadcast::Sender<Arc<Message>>;
+
+impl<S: Clone, R: Id, C: Id> Room<S, R, C> {
+    /// sets how the room sends a message to it's connections, default is `FanOut::Direct`. The connections that already in the room are subscribed to the new channel, or unsubscribed if it's `FanOut::Direct`.
+    ///
+    /// ```rust,ignore
+    ///
+    /// use actix_wsb::FanOut;
+    ///
+    /// broadcaster.write().unwrap().handle_room(&room_id).set_fan_out(FanOut::Channel(1024));
+    ///
+    /// ```
+    pub fn set_fan_out(&mut self, fan_out: FanOut) {
+        self.fan_out = fan_out;
+        self.channel = match fan_out {
+            FanOut::Direct => None,
+            FanOut::Channel(capacity) => Some(broadcast::channel(capacity.max(1)).0)
+        };
+
+        for connection in self.connectors.values() {
+            match self.channel {
+                Some(_) => self.subscribe(connection),
+                None => connection.outbound.unsubscribe(self.key)
+            }
+        }
+    }
+
+    /// returns how the room sends a message to it's connections.
+    pub fn fan_out(&self) -> FanOut {
+        self.fan_out
+    }
+
+    /// subscribes the writer task of the connection to the channel of the room, if the room has one.
+    pub(crate) fn subscribe(&self, connection: &Connection<S, C>) {
+        let Some(channel) = &self.channel else { return };
+
+        let events = self.events.clone();
+        let room = self.id.clone();
+        let id = connection.id.clone();
+
+        connection.outbound.subscribe(self.key, Subscription {
+            receiver: channel.subscribe(),
+            on_lag: Box::new(move |missed| events.emit(Event::Lagged { room: room.clone(), connection: id.clone(), missed }))
+        });
+    }
+
+    /// publishes the message to the channel of the room and returns the report of it, or gives the message back if the room doesn't have a channel.
+    pub(crate) fn publish(&self, message: Message) -> Result<DeliveryReport<C>, Message> {
+        match &self.channel {
+            Some(channel) => Ok(DeliveryReport::<C> {
+                subscribers: channel.send(Arc::new(message)).unwrap_or(0),
+                ..Default::default()
+            }),
+            None => Err(message)
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// the close frames of the connections that are removed from their room already, they're sent when it's awaited. The methods that close connections under the lock of a broadcaster return it instead of being async, so the lock can be released before awaiting the clients:
+///
+/// ```rust,ignore
+///
+/// // the write lock is released at the end of that statement:
+/// let closing = broadcaster.write().unwrap().room(&room_id)?.close(None);
+///
+/// let report = closing.await;
+///
+/// ```
+///
+/// Awaiting it in the same statement holds the lock until the clients read the close frames, 5 seconds at most for each of them. If it's dropped without awaiting, the connections are removed but their close frames aren't sent, their sessions are closed when the clients go away.
+#[must_use = "the close frames are sent only when it's awaited"]
+pub struct Closing<T> {
+    future: Pin<Box<dyn Future<Output = T> + Send>>
+}
+
+impl<T: Send + 'static> Closing<T> {
+    pub(crate) fn new(future: impl Future<Output = T> + Send + 'static) -> Self {
+        Self { future: Box::pin(future) }
+    }
+
+    /// a closing that has nothing to close, it returns given output right away.
+    pub(crate) fn ready(output: T) -> Self {
+        Self::new(async move { output })
+    }
+}
+
+impl<T> Future for Closing<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.future.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,32 @@
+use actix_ws::{CloseCode, CloseReason};
+
+/// what happens when a session is added to a room with the id of a connection that's already in it.
+///
+/// ```rust
+///
+/// use actix_wsb::{Broadcaster, DuplicatePolicy};
+///
+/// let broadcaster = Broadcaster::new();
+///
+/// // the new session is closed and the old one stays:
+/// broadcaster.write().unwrap().set_duplicate_policy(DuplicatePolicy::Reject(None));
+///
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// the new session is closed with given reason and the existing connection stays.
+    Reject(Option<CloseReason>),
+    /// the existing connection is closed with given reason and the new session takes it's place, like a single session login or a client that reconnects before it's old session times out. The messages that waiting in the queue of the old one are discarded. This is the default, with `CloseCode::Normal` and "replaced by a new session" description.
+    Replace(Option<CloseReason>),
+    /// the new session is added to the existing connection, for multiple tabs of the same user. The messages sent to the connection are sent to every session of it, and a session that's closed is dropped when a send to it fails. The metadata and state of the existing connection are kept. Use `.close_session()` methods to close a single session, `.disconnect()` closes all of them.
+    Allow
+}
+
+impl Default for DuplicatePolicy {
+    fn default() -> Self {
+        Self::Replace(Some(CloseReason {
+            code: CloseCode::Normal,
+            description: Some("replaced by a new session".to_string())
+        }))
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt;
+use std::sync::PoisonError;
+use actix_ws::CloseReason;
+
+/// the error type of the broadcaster. Every fallible operation of `Connection`, `Room` and `Broadcaster` returns it instead of panicking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the websocket session is closed, which means client is disconnected or session is closed before.
+    Closed,
+    /// the lock of the broadcaster is poisoned, which means another thread panicked while it was holding that lock.
+    LockPoisoned,
+    /// there is no room with given id.
+    RoomNotFound(String),
+    /// there is no connection with given id.
+    ConnectionNotFound(String),
+    /// the outbound queue of the connection is full because the client can't keep up, so the message is dropped.
+    QueueFull,
+    /// the connection is closed because it's outbound queue was full, by `SlowConsumerPolicy::Disconnect`.
+    Disconnected,
+    /// the outbound queue of the connection didn't have room during the timeout of `SlowConsumerPolicy::Block`, so the message is dropped. Closing a connection returns it too, if the client doesn't read the queued messages and the close frame in 5 seconds, in that case it's session is dropped without the close frame.
+    Timeout,
+    /// message couldn't be sent to some of the connections of a room. It holds the id's of that connections, the message is queued for the others.
+    PartialDelivery(Vec<String>),
+    /// a hook that registered with `.on_join()` rejected the connection, so it's session is closed with that reason and it's not added to the room.
+    Rejected(CloseReason),
+    /// a session is added with the id of a connection that's already in the room and `DuplicatePolicy::Reject` closed it. It holds the id of the connection.
+    Duplicate(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Closed => f.write_str("session is closed"),
+            Error::LockPoisoned => f.write_str("broadcaster lock is poisoned"),
+            Error::RoomNotFound(id) => write!(f, "room \"{}\" is not found", id),
+            Error::ConnectionNotFound(id) => write!(f, "connection \"{}\" is not found", id),
+            Error::QueueFull => f.write_str("outbound queue of the connection is full"),
+            Error::Disconnected => f.write_str("connection is disconnected because it couldn't keep up"),
+            Error::Timeout => f.write_str("client of the connection didn't read it's messages until the timeout"),
+            Error::PartialDelivery(ids) => write!(f, "message couldn't be delivered to {} connection(s): {}", ids.len(), ids.join(", ")),
+            Error::Rejected(reason) => match &reason.description {
+                Some(description) => write!(f, "joining the room is rejected: {}", description),
+                None => write!(f, "joining the room is rejected with close code {}", u16::from(reason.code))
+            },
+            Error::Duplicate(id) => write!(f, "connection \"{}\" is already in the room", id),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<actix_ws::Closed> for Error {
+    fn from(_: actix_ws::Closed) -> Self {
+        Error::Closed
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Error::LockPoisoned
+    }
+}
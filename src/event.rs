@@ -0,0 +1,55 @@
+use std::sync::{Arc, RwLock};
+
+/// the events that broadcaster emits to the listeners that registered with `.on_event()` method of `Broadcaster`. `R` and `C` are the types of the room and connection id's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<R = String, C = String> {
+    /// a connection is removed from it's room automatically, because it's session was closed when a message is sent to it or it's disconnected by `SlowConsumerPolicy::Disconnect`.
+    Pruned { room: R, connection: C },
+    /// a connection is closed and removed from it's room by the heartbeat supervisor, because it didn't answer the pings.
+    TimedOut { room: R, connection: C },
+    /// a connection of a `FanOut::Channel` room fell behind the channel of the room, so it missed given count of messages.
+    Lagged { room: R, connection: C, missed: u64 },
+    /// a connection is added to the room, with `.handle()`, `.add_connection()`, `.join()` or `.move_connection()`. It's not emitted for a session that attached to an existing connection by `DuplicatePolicy::Allow`.
+    Joined { room: R, connection: C },
+    /// a connection is removed from the room without closing, with `.remove_connection()`, `.leave()` or `.move_connection()`.
+    Left { room: R, connection: C },
+    /// a connection is closed and removed from the room, with `.disconnect()`, `.close_conn()`, one of the `.close()` methods or `.remove_room()`, or it's replaced by a new one by `DuplicatePolicy::Replace`.
+    Closed { room: R, connection: C },
+    /// a room is created, by `.handle_room()` or by a method that creates the room when it's not exist.
+    RoomCreated { room: R },
+    /// a room is removed with `.remove_room()` or `.remove_empty_rooms()`. The `Event::Closed`'s of it's connections are emitted before it.
+    RoomRemoved { room: R },
+}
+
+type Listener<R, C> = Arc<dyn Fn(&Event<R, C>) + Send + Sync>;
+
+/// the listeners that shared between the broadcaster and it's rooms, so the listeners that registered later are also called from the rooms that created before.
+pub(crate) struct Listeners<R, C>(Arc<RwLock<Vec<Listener<R, C>>>>);
+
+impl<R, C> Clone for Listeners<R, C> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<R, C> Default for Listeners<R, C> {
+    fn default() -> Self {
+        Self(Arc::default())
+    }
+}
+
+impl<R, C> Listeners<R, C> {
+    pub(crate) fn push<F>(&self, listener: F) where F: Fn(&Event<R, C>) + Send + Sync + 'static {
+        self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Arc::new(listener));
+    }
+
+    /// calls every listener with given event. Listeners are copied before they called, so a listener can register another listener without a deadlock.
+    pub(crate) fn emit(&self, event: Event<R, C>) {
+        let listeners = self.0.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+
+        for listener in listeners {
+            listener(&event);
+        }
+    }
+}
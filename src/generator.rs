@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use ulid::Ulid;
+use uuid::Uuid;
+
+/// how a broadcaster generates the id's of the connections that added without one, with the `.handle_generated()` methods. Generated id's are unique, so a client can't take the id of another one by sending it in a query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdGenerator {
+    /// random version 4 uuid's, like "67e55044-10b1-426f-9247-bb680e5fe0c8". This is the default.
+    #[default]
+    UuidV4,
+    /// ulid's, which are sorted by the time they're generated, like "01ARZ3NDEKTSV4RRFFQ69G5FAV".
+    Ulid,
+    /// a counter that starts from 1 and increases with every generated id. It's unique only inside of the broadcaster that generates it.
+    Counter
+}
+
+/// the id generator of a broadcaster, the counter is shared between it's copies.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ids {
+    pub(crate) generator: IdGenerator,
+    counter: Arc<AtomicU64>
+}
+
+impl Ids {
+    /// generates a new id.
+    pub(crate) fn next(&self) -> String {
+        match self.generator {
+            IdGenerator::UuidV4 => Uuid::new_v4().to_string(),
+            IdGenerator::Ulid => Ulid::new().to_string(),
+            IdGenerator::Counter => (self.counter.fetch_add(1, Ordering::Relaxed) + 1).to_string()
+        }
+    }
+}
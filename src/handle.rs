@@ -0,0 +1,362 @@
+use std::borrow::Borrow;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use actix_ws::{CloseReason, Message, Session};
+use actix_web::rt::task::JoinHandle;
+use actix_web::HttpRequest;
+
+use crate::surface::send_methods;
+use crate::{close_memberships, close_released, fan_out, Broadcaster, Connection, DeliveryReport, Error, Event, Handshake, HeartbeatConfig, Id, Metadata, Room};
+
+/// a cheap, cloneable handle of a `Broadcaster`. Unlike using `Arc<RwLock<Broadcaster>>` directly, it never holds the lock across an `.await`: it copies the target sessions of a room under a short lock, releases it and sends the messages after that. So a slow broadcast doesn't block the other actix workers and the returned futures are `Send`.
+///
+/// ```rust
+///
+/// use actix_wsb::BroadcasterHandle;
+///
+/// fn main () {
+///     let broadcaster = BroadcasterHandle::new();
+///
+///     // clone it for each worker, it's only an `Arc` under the hood:
+///     let worker_broadcaster = broadcaster.clone();
+///
+///     assert!(!worker_broadcaster.read().unwrap().check(&"1".to_string()));
+/// }
+///
+/// ```
+pub struct BroadcasterHandle<S = (), R = String, C = String> {
+    inner: Arc<RwLock<Broadcaster<S, R, C>>>
+}
+
+impl<S, R, C> Clone for BroadcasterHandle<S, R, C> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<S, R, C> Default for BroadcasterHandle<S, R, C> {
+    fn default() -> Self {
+        Self { inner: Arc::new(RwLock::new(Broadcaster::default())) }
+    }
+}
+
+impl BroadcasterHandle {
+    /// creates a handle with an empty broadcaster.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Clone, R: Id, C: Id> BroadcasterHandle<S, R, C> {
+    /// creates a handle with an empty broadcaster whose connections have a state of type `S`, it's the equivalent of `Broadcaster::typed()`.
+    pub fn typed() -> Self {
+        Self::default()
+    }
+
+    /// locks the broadcaster for reading. Don't hold the returned guard across an `.await`.
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, Broadcaster<S, R, C>>, Error> {
+        Ok(self.inner.read()?)
+    }
+
+    /// locks the broadcaster for writing. Don't hold the returned guard across an `.await`.
+    pub fn write(&self) -> Result<RwLockWriteGuard<'_, Broadcaster<S, R, C>>, Error> {
+        Ok(self.inner.write()?)
+    }
+
+    /// adds the session to the room with given id as a connection with given id, creates the room if it's not exist. It's the equivalent of `Broadcaster::handle()`.
+    ///
+    ///```rust,ignore
+    ///
+    /// let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
+    ///
+    /// broadcaster.handle(&room_id, &id, session)?;
+    ///
+    ///```
+    pub fn handle<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, Metadata::default(), S::default(), None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the connection has given metadata. It's the equivalent of `Broadcaster::handle_with()`.
+    ///
+    ///```rust,ignore
+    ///
+    /// broadcaster.handle_with(&room_id, &id, session, Metadata::new().user_id(&user.id).role("admin"))?;
+    ///
+    ///```
+    pub fn handle_with<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, metadata, S::default(), None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the connection has given state. It's the equivalent of `Broadcaster::handle_with_state()`.
+    pub fn handle_with_state<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, Metadata::default(), state, None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the join hooks get the handshake request of the connection. It's the equivalent of `Broadcaster::handle_request()`.
+    ///
+    ///```rust,ignore
+    ///
+    /// let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
+    ///
+    /// broadcaster.handle_request(&req, &room_id, &id, session)?;
+    ///
+    ///```
+    pub fn handle_request<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, Metadata::default(), S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// same as `.handle_request()`, but the connection has given metadata. It's the equivalent of `Broadcaster::handle_request_with()`.
+    pub fn handle_request_with<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, metadata, S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// same as `.handle_request()`, but the connection has given state. It's the equivalent of `Broadcaster::handle_request_with_state()`.
+    pub fn handle_request_with_state<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.write()?.admit(room_id, conn_id, session, Metadata::default(), state, Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// starts the heartbeat supervisor of the broadcaster, it's the equivalent of `Broadcaster::heartbeat()`. The supervisor stops when every clone of the handle is dropped.
+    ///
+    ///```rust,ignore
+    ///
+    /// let broadcaster = BroadcasterHandle::new();
+    ///
+    /// broadcaster.heartbeat(HeartbeatConfig::default());
+    ///
+    ///```
+    pub fn heartbeat(&self, config: HeartbeatConfig) -> JoinHandle<()> where S: Send + Sync + 'static {
+        Broadcaster::heartbeat(&self.inner, config)
+    }
+
+    /// records that a pong with given payload is received from the connection with given id. It's the equivalent of `.record_pong()` method of `Broadcaster`.
+    pub fn record_pong<K>(&self, conn_id: &K, bytes: &[u8]) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.read()?.record_pong(conn_id, bytes)
+    }
+
+    /// changes the metadata of the connection with given id in every room that it's in. It's the equivalent of `.update_metadata()` method of `Broadcaster`.
+    pub fn update_metadata<F, K>(&self, conn_id: &K, f: F) -> Result<(), Error> where F: FnMut(&mut Metadata), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.write()?.update_metadata(conn_id, f)
+    }
+
+    /// changes the state of the connection with given id in every room that it's in. It's the equivalent of `.update_state()` method of `Broadcaster`.
+    pub fn update_state<F, K>(&self, conn_id: &K, f: F) -> Result<(), Error> where F: FnMut(&mut S), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.write()?.update_state(conn_id, f)
+    }
+
+    /// adds the connection with given id to the room with given id too, creates the room if it's not exist. It's the equivalent of `.join()` method of `Broadcaster`.
+    ///
+    ///```rust,ignore
+    ///
+    /// broadcaster.handle(&format!("user-{}", user_id), &id, session)?;
+    ///
+    /// broadcaster.join(&team_id, &id)?;
+    /// broadcaster.join("notifications", &id)?;
+    ///
+    ///```
+    pub fn join<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.write()?.join(room_id, conn_id)
+    }
+
+    /// removes the connection with given id from the room with given id without closing it. It's the equivalent of `.leave()` method of `Broadcaster`.
+    pub fn leave<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.write()?.leave(room_id, conn_id)
+    }
+
+    /// returns the id's of the rooms that the connection with given id is in.
+    pub fn rooms_of<K>(&self, conn_id: &K) -> Result<Vec<R>, Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        Ok(self.read()?.rooms_of(conn_id))
+    }
+
+    /// moves the connection with given id from a room to another one under the write lock, so no broadcast can happen in the middle of it. It's the equivalent of `.move_connection()` method of `Broadcaster`.
+    pub fn move_connection<K, Q>(&self, conn_id: &K, from: &Q, to: &Q) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        self.write()?.move_connection(conn_id, from, to)
+    }
+
+    /// closes the connection with given id and removes it from every room that it's in, the lock isn't held while the close frame is sent. It's the equivalent of `.disconnect()` method of `Broadcaster`.
+    ///
+    ///```rust,ignore
+    ///
+    /// Message::Close(reason) => {
+    ///     let _ = broadcaster.disconnect(reason, &id).await;
+    ///
+    ///     break;
+    /// },
+    ///
+    ///```
+    pub async fn disconnect<K>(&self, reason: Option<CloseReason>, conn_id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let memberships = self.write()?.take_all(conn_id, Room::evict);
+
+        close_memberships(memberships, reason, conn_id).await
+    }
+
+    /// closes given session of the connection with given id, or the whole connection if that was it's last session. It's the equivalent of `.close_session()` method of `Broadcaster`.
+    pub async fn close_session<K>(&self, reason: Option<CloseReason>, conn_id: &K, session: Session) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.read()?.find(conn_id);
+
+        match connection {
+            Some(connection) if connection.outbound.release() => close_released(session, reason).await,
+            Some(_) => self.disconnect(reason, conn_id).await,
+            None => Err(Error::ConnectionNotFound(conn_id.to_string()))
+        }
+    }
+
+    send_methods! {
+        receiver: [],
+        room: [room_id: Q],
+        generics: [Q],
+        bounds: [R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized],
+        condition: [],
+        output: Result<DeliveryReport<C>, Error>,
+        close: [async await] Result<DeliveryReport<C>, Error>,
+        ///
+        ///```rust,ignore
+        ///
+        /// Message::Text(msg) => {
+        ///     let report = broadcaster.broadcast(&room_id, msg.to_string()).await?;
+        /// },
+        ///
+        ///```
+    }
+
+    /// closes the connection with given id and removes it from the room.
+    ///
+    ///```rust,ignore
+    ///
+    /// Message::Close(reason) => {
+    ///     let _ = broadcaster.close_conn(&room_id, reason, &id).await;
+    ///
+    ///     break;
+    /// },
+    ///
+    ///```
+    pub async fn close_conn<Q, K>(&self, room_id: &Q, reason: Option<CloseReason>, id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let closing = self.write()?.room(room_id)?.close_conn(reason, id);
+
+        closing.await
+    }
+
+    /// removes the room with given id and closes all of it's connections concurrently after releasing the lock.
+    pub async fn remove_room<Q>(&self, room_id: &Q) -> Result<DeliveryReport<C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let mut room = self.write()?.rooms.remove(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+        let closing = room.close(None);
+        room.events.emit(Event::RoomRemoved { room: room.id.clone() });
+
+        Ok(closing.await)
+    }
+
+    /// publishes the message to the channel of the room under a read lock if it has one, otherwise sends it to every connector like `.dispatch()`. The connectors whose writer task is stopped are pruned under a write lock after publishing.
+    async fn send_all<Q>(&self, room_id: &Q, message: Message) -> Result<DeliveryReport<C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let (published, dead) = {
+            let broadcaster = self.read()?;
+            let room = broadcaster.rooms.get(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+            (room.publish(message), room.has_dead())
+        };
+
+        match published {
+            Ok(mut report) => {
+                if dead {
+                    if let Some(room) = self.write()?.check_room(room_id) {
+                        report.pruned = room.reap();
+                    }
+                }
+
+                Ok(report)
+            },
+            Err(message) => self.dispatch(room_id, message, |_| true).await
+        }
+    }
+
+    /// copies the targets of the room under a read lock, sends the message to them without holding any lock and then prunes the closed ones under a short write lock.
+    async fn dispatch<F, Q>(&self, room_id: &Q, message: Message, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let mut snapshot = self.snapshot(room_id, condition)?;
+        let mut report = DeliveryReport::<C> { skipped: snapshot.skipped, ..Default::default() };
+
+        fan_out(&mut snapshot.targets, &message, snapshot.concurrency, &mut report).await;
+
+        if !report.pruned.is_empty() || snapshot.dead {
+            if let Some(room) = self.write()?.check_room(room_id) {
+                room.settle(&mut report);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// removes the connections to close from the room under the write lock of the broadcaster and closes them after releasing it.
+    async fn close_where<F, Q>(&self, room_id: &Q, reason: Option<CloseReason>, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let closing = self.write()?.room(room_id)?.close_where(reason, condition);
+
+        Ok(closing.await)
+    }
+
+    /// copies the connections of the room that satisfies the condition and the concurrency limit of the room under a read lock.
+    fn snapshot<F, Q>(&self, room_id: &Q, condition: F) -> Result<Snapshot<S, C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let broadcaster = self.read()?;
+        let room = broadcaster.rooms.get(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+        Ok(Snapshot::of(room, condition))
+    }
+}
+
+impl<S: Clone, R: Id> BroadcasterHandle<S, R, String> {
+    /// same as `.handle()`, but the broadcaster generates a unique id for the connection and returns it. It's the equivalent of `Broadcaster::handle_generated()`.
+    ///
+    ///```rust,ignore
+    ///
+    /// let id = broadcaster.handle_generated(&room_id, session)?;
+    ///
+    ///```
+    pub fn handle_generated<Q>(&self, room_id: &Q, session: Session) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        Broadcaster::handle_generated(&self.inner, room_id, session)
+    }
+
+    /// same as `.handle_generated()`, but the connection has given metadata.
+    pub fn handle_generated_with<Q>(&self, room_id: &Q, session: Session, metadata: Metadata) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        Broadcaster::handle_generated_with(&self.inner, room_id, session, metadata)
+    }
+
+    /// same as `.handle_generated()`, but the connection has given state.
+    pub fn handle_generated_with_state<Q>(&self, room_id: &Q, session: Session, state: S) -> Result<String, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        Broadcaster::handle_generated_with_state(&self.inner, room_id, session, state)
+    }
+}
+
+/// the copy of a room's targets that taken under the lock.
+pub(crate) struct Snapshot<S, C> {
+    pub(crate) targets: Vec<Connection<S, C>>,
+    pub(crate) skipped: Vec<C>,
+    pub(crate) concurrency: Option<usize>,
+    /// the room has connectors whose writer task is stopped, they're pruned after the send.
+    pub(crate) dead: bool
+}
+
+impl<S: Clone, C: Id> Snapshot<S, C> {
+    /// copies the connections of the room that satisfies the condition and the concurrency limit of the room.
+    pub(crate) fn of<R: Id, F>(room: &Room<S, R, C>, condition: F) -> Self where F: Fn(&Connection<S, C>) -> bool {
+        let (targets, skipped) = room.select(condition);
+
+        Self { targets, skipped, concurrency: room.concurrency, dead: room.has_dead() }
+    }
+}
+
+impl<S, R, C> From<Arc<RwLock<Broadcaster<S, R, C>>>> for BroadcasterHandle<S, R, C> {
+    /// wraps a broadcaster that created with `Broadcaster::new()`, so the existing code can migrate gradually.
+    fn from(inner: Arc<RwLock<Broadcaster<S, R, C>>>) -> Self {
+        Self { inner }
+    }
+}
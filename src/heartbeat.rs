@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
+use actix_web::rt::time::interval;
+use actix_ws::{CloseCode, CloseReason, Message};
+
+use crate::{Broadcaster, Connection, Event, Id, Room};
+
+/// settings of the heartbeat supervisor that started with `Broadcaster::heartbeat()` or `.heartbeat()` method of `BroadcasterHandle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// how often every connection is pinged. Default is 10 seconds.
+    pub interval: Duration,
+    /// how many pings in a row a connection can leave unanswered before it's closed and removed. Default is 3.
+    pub max_missed: u32,
+    /// the reason that is sent to the connections that closed by the supervisor. Default is `CloseCode::Away` with "heartbeat timeout" description.
+    pub close_reason: Option<CloseReason>
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            max_missed: 3,
+            close_reason: Some(CloseReason {
+                code: CloseCode::Away,
+                description: Some("heartbeat timeout".to_string())
+            })
+        }
+    }
+}
+
+/// the liveness of a connection, it's shared between the clones of it.
+pub(crate) struct Liveness {
+    last_pong: Mutex<Instant>,
+    missed: AtomicU32
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self {
+            last_pong: Mutex::new(Instant::now()),
+            missed: AtomicU32::new(0)
+        }
+    }
+}
+
+impl Liveness {
+    pub(crate) fn last_pong(&self) -> Instant {
+        *self.last_pong.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn missed(&self) -> u32 {
+        self.missed.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_pong(&self) {
+        *self.last_pong.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Instant::now();
+        self.missed.store(0, Ordering::Relaxed);
+    }
+
+    fn record_ping(&self) {
+        self.missed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// a connection that missed too many heartbeats with the id of it's room.
+pub(crate) type Expired<S, R, C> = (R, Connection<S, C>);
+
+/// the connections that missed too many heartbeats, and the ones that should be pinged.
+pub(crate) type Targets<S, R, C> = (Vec<Expired<S, R, C>>, Vec<Connection<S, C>>);
+
+/// the broadcasters that the heartbeat supervisor can watch.
+pub(crate) trait Supervised: Send + Sync + 'static {
+    /// the type of the state of the connections.
+    type State: Clone;
+    /// the type of the id's of the rooms.
+    type RoomId: Id;
+    /// the type of the id's of the connections.
+    type ConnId: Id;
+
+    /// collects the targets of a heartbeat, returns `None` if the broadcaster can't be locked anymore.
+    fn heartbeat_targets(&self, max_missed: u32) -> Option<Targets<Self::State, Self::RoomId, Self::ConnId>>;
+
+    /// removes the expired connections from their rooms and closes them, returns false if the broadcaster can't be locked anymore.
+    fn expire(&self, expired: &[Expired<Self::State, Self::RoomId, Self::ConnId>], reason: &Option<CloseReason>) -> bool;
+}
+
+/// the heartbeat supervisor task. It stops when the broadcaster is dropped.
+pub(crate) async fn supervise<T: Supervised>(broadcaster: Weak<T>, config: HeartbeatConfig) {
+    let mut ticks = interval(config.interval);
+
+    // the first tick completes immediately, connections should have time to answer before the first check.
+    ticks.tick().await;
+
+    loop {
+        ticks.tick().await;
+
+        let Some(broadcaster) = broadcaster.upgrade() else { return };
+        let Some((expired, alive)) = broadcaster.heartbeat_targets(config.max_missed) else { return };
+
+        if !expired.is_empty() && !broadcaster.expire(&expired, &config.close_reason) {
+            return;
+        }
+
+        // the memberships of a connection in many rooms share it's outbound queue and liveness, so it's pinged once.
+        let mut pinged = HashSet::new();
+
+        for connection in alive {
+            if !pinged.insert(Arc::as_ptr(&connection.outbound)) {
+                continue;
+            }
+
+            connection.liveness.record_ping();
+
+            // the pings are timestamped, so their pongs are also used to measure the round-trip time. If the queue is full the ping is skipped, so it counts as missed.
+            let _ = connection.outbound.try_push(Message::Ping(connection.latency.stamp()));
+        }
+    }
+}
+
+/// splits the connections of given rooms to the expired ones and the ones that should be pinged.
+pub(crate) fn collect_targets<'a, S: Clone + 'a, R: Id, C: Id>(rooms: impl Iterator<Item = &'a Room<S, R, C>>, max_missed: u32, targets: &mut Targets<S, R, C>) {
+    for room in rooms {
+        for connection in room.connectors.values() {
+            if connection.liveness.missed() >= max_missed {
+                targets.0.push((room.id.clone(), connection.clone()));
+            } else {
+                targets.1.push(connection.clone());
+            }
+        }
+    }
+}
+
+impl<S: Clone, R: Id, C: Id> Room<S, R, C> {
+    /// removes the connection if it's still in the room, closes it without waiting and emits an `Event::TimedOut` for it.
+    pub(crate) fn time_out(&mut self, connection: &Connection<S, C>, reason: &Option<CloseReason>) {
+        if self.detach(connection) {
+            connection.outbound.abort(reason.clone());
+
+            self.events.emit(Event::TimedOut { room: self.id.clone(), connection: connection.id.clone() });
+        }
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static, R: Id, C: Id> Supervised for RwLock<Broadcaster<S, R, C>> {
+    type State = S;
+    type RoomId = R;
+    type ConnId = C;
+
+    fn heartbeat_targets(&self, max_missed: u32) -> Option<Targets<S, R, C>> {
+        let broadcaster = self.read().ok()?;
+        let mut targets = Targets::default();
+
+        collect_targets(broadcaster.rooms.values(), max_missed, &mut targets);
+
+        Some(targets)
+    }
+
+    fn expire(&self, expired: &[Expired<S, R, C>], reason: &Option<CloseReason>) -> bool {
+        let Ok(mut broadcaster) = self.write() else { return false };
+
+        for (room_id, connection) in expired {
+            if let Some(room) = broadcaster.check_room(room_id) {
+                room.time_out(connection, reason);
+            }
+        }
+
+        true
+    }
+}
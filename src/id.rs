@@ -0,0 +1,7 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// the types that can be used as the id of a room or a connection, like `String`, `u64` or `Uuid`. It's implemented for every type that satisfies it's bounds, so you don't have to implement it yourself. `Display` is needed for the messages of the errors that hold an id.
+pub trait Id: Hash + Eq + Clone + Display + Debug + Send + Sync + 'static {}
+
+impl<T> Id for T where T: Hash + Eq + Clone + Display + Debug + Send + Sync + 'static {}
@@ -0,0 +1,64 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::Id;
+
+/// how many independently locked parts the index has, so joins and leaves of different connections rarely wait for each other.
+const PARTS: usize = 16;
+
+type Part<R, C> = Mutex<HashMap<C, HashSet<R>>>;
+
+/// the reverse index from connection id's to the id's of the rooms they're in. It's shared between the broadcaster and it's rooms like the listeners, so the rooms keep it up to date when connections are added or removed.
+pub(crate) struct Index<R, C>(Arc<Parts<R, C>>);
+
+struct Parts<R, C> {
+    parts: Vec<Part<R, C>>,
+    hasher: RandomState
+}
+
+impl<R, C> Clone for Index<R, C> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<R, C> Default for Index<R, C> {
+    fn default() -> Self {
+        Self(Arc::new(Parts {
+            parts: (0..PARTS).map(|_| Part::default()).collect(),
+            hasher: RandomState::new()
+        }))
+    }
+}
+
+impl<R: Id, C: Id> Index<R, C> {
+    fn lock<Q>(&self, conn_id: &Q) -> MutexGuard<'_, HashMap<C, HashSet<R>>> where Q: Hash + ?Sized {
+        let part = self.0.hasher.hash_one(conn_id) as usize % PARTS;
+
+        self.0.parts[part].lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn insert(&self, conn_id: &C, room_id: &R) {
+        self.lock(conn_id).entry(conn_id.clone()).or_default().insert(room_id.clone());
+    }
+
+    pub(crate) fn remove(&self, conn_id: &C, room_id: &R) {
+        let mut index = self.lock(conn_id);
+
+        if let Some(rooms) = index.get_mut(conn_id) {
+            rooms.remove(room_id);
+
+            if rooms.is_empty() {
+                index.remove(conn_id);
+            }
+        }
+    }
+
+    /// returns the id's of the rooms that the connection with given id is in.
+    pub(crate) fn rooms_of<Q>(&self, conn_id: &Q) -> Vec<R> where C: Borrow<Q>, Q: Hash + Eq + ?Sized {
+        self.lock(conn_id).get(conn_id).map(|rooms| rooms.iter().cloned().collect()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use actix_web::web::Bytes;
+
+/// the prefix of the ping payloads that the broadcaster sends to measure the round-trip time, so they're not mixed with your own pings.
+const MAGIC: &[u8; 4] = b"wsbt";
+
+/// how many round-trip times are kept to calculate the average and the 99th percentile.
+const WINDOW: usize = 128;
+
+/// how many timed pings can wait for their pong at the same time. Older ones are forgotten.
+const MAX_PENDING: usize = 16;
+
+/// round-trip time statistics of a connection, or of all the connections of a room. `min` is the lowest one since the connection is created, `avg` and `p99` are calculated from the last 128 measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttStats {
+    /// the latest measured round-trip time.
+    pub last: Duration,
+    /// the lowest round-trip time ever measured.
+    pub min: Duration,
+    /// the average of the recent round-trip times.
+    pub avg: Duration,
+    /// the 99th percentile of the recent round-trip times.
+    pub p99: Duration,
+    /// how many recent measurements that `avg` and `p99` are calculated from.
+    pub samples: usize
+}
+
+/// the round-trip time measurements of a connection, it's shared between the clones of it.
+pub(crate) struct Latency {
+    epoch: Instant,
+    state: Mutex<State>
+}
+
+#[derive(Default)]
+struct State {
+    /// the timestamps of the pings that waiting for their pong, in microseconds since the epoch.
+    pending: VecDeque<u64>,
+    /// the recent round-trip times.
+    window: VecDeque<Duration>,
+    min: Option<Duration>,
+    last: Option<(Duration, Instant)>
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            state: Mutex::new(State::default())
+        }
+    }
+}
+
+impl Latency {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// creates a timestamped ping payload and waits for it's pong.
+    pub(crate) fn stamp(&self) -> Bytes {
+        let stamp = self.epoch.elapsed().as_micros() as u64;
+        let mut state = self.lock();
+
+        if state.pending.len() == MAX_PENDING {
+            state.pending.pop_front();
+        }
+
+        state.pending.push_back(stamp);
+
+        let mut payload = Vec::with_capacity(MAGIC.len() + 8);
+
+        payload.extend_from_slice(MAGIC);
+        payload.extend_from_slice(&stamp.to_be_bytes());
+
+        payload.into()
+    }
+
+    /// matches the pong payload with a timed ping and records the round-trip time of it. Returns false if the payload isn't one of the pings that waiting.
+    pub(crate) fn record(&self, payload: &[u8]) -> bool {
+        let Some(stamp) = payload.strip_prefix(MAGIC.as_slice())
+                                 .and_then(|stamp| <[u8; 8]>::try_from(stamp).ok())
+                                 .map(u64::from_be_bytes) else { return false };
+
+        let now = Instant::now();
+        let mut state = self.lock();
+
+        let Some(position) = state.pending.iter().position(|pending| *pending == stamp) else { return false };
+
+        // a client may answer only the latest ping, so the ones before it will never be answered.
+        state.pending.drain(..=position);
+
+        let rtt = now.duration_since(self.epoch).saturating_sub(Duration::from_micros(stamp));
+
+        if state.window.len() == WINDOW {
+            state.window.pop_front();
+        }
+
+        state.window.push_back(rtt);
+        state.min = Some(state.min.map_or(rtt, |min| min.min(rtt)));
+        state.last = Some((rtt, now));
+
+        true
+    }
+
+    pub(crate) fn stats(&self) -> Option<RttStats> {
+        let state = self.lock();
+        let (last, _) = state.last?;
+
+        Some(summarize(last, state.min.unwrap_or(last), state.window.iter().copied().collect()))
+    }
+
+    /// aggregates the measurements of given connections: `last` is the latest measurement of any of them and the others are calculated from their recent measurements together.
+    pub(crate) fn aggregate<'a>(latencies: impl Iterator<Item = &'a Latency>) -> Option<RttStats> {
+        let mut last: Option<(Duration, Instant)> = None;
+        let mut min: Option<Duration> = None;
+        let mut samples = vec![];
+
+        for latency in latencies {
+            let state = latency.lock();
+
+            if let Some((rtt, at)) = state.last {
+                if last.map_or(true, |(_, latest)| at > latest) {
+                    last = Some((rtt, at));
+                }
+            }
+
+            if let Some(connection_min) = state.min {
+                min = Some(min.map_or(connection_min, |min| min.min(connection_min)));
+            }
+
+            samples.extend(state.window.iter().copied());
+        }
+
+        let (last, _) = last?;
+
+        Some(summarize(last, min.unwrap_or(last), samples))
+    }
+}
+
+fn summarize(last: Duration, min: Duration, mut samples: Vec<Duration>) -> RttStats {
+    samples.sort_unstable();
+
+    let avg = samples.iter().sum::<Duration>() / samples.len().max(1) as u32;
+    let p99 = samples.get((samples.len() * 99).div_ceil(100).saturating_sub(1)).copied().unwrap_or(last);
+
+    RttStats { last, min, avg, p99, samples: samples.len() }
+}
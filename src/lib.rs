@@ -1,687 +1,1614 @@
-use std::sync::{Arc, RwLock};
-use actix_ws::{CloseReason, Item, Session};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use actix_ws::{CloseReason, Item, Message, Session};
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::timeout;
 use actix_web::web::Bytes;
+use actix_web::HttpRequest;
+use futures_util::{stream, Future, StreamExt};
 
+mod actor;
+mod admission;
+mod channel;
+mod closing;
+mod duplicate;
+mod error;
+mod event;
+mod generator;
+mod handle;
+mod heartbeat;
+mod id;
+mod index;
+mod latency;
+mod metadata;
+mod presence;
+mod queue;
+mod report;
+mod shard;
+mod surface;
+
+pub use actor::{RoomHandle, RoomRouter};
+pub use admission::{Handshake, JoinRequest};
+pub use bytestring::ByteString;
+pub use channel::FanOut;
+pub use closing::Closing;
+pub use duplicate::DuplicatePolicy;
+pub use error::Error;
+pub use event::Event;
+pub use generator::IdGenerator;
+pub use handle::BroadcasterHandle;
+pub use heartbeat::HeartbeatConfig;
+pub use id::Id;
+pub use latency::RttStats;
+pub use metadata::Metadata;
+pub use queue::SlowConsumerPolicy;
+pub use report::DeliveryReport;
+pub use shard::{RoomGuard, ShardedBroadcaster};
+
+use admission::JoinHooks;
+use channel::Channel;
+use event::Listeners;
+use generator::Ids;
+use heartbeat::Liveness;
+use index::Index;
+use latency::Latency;
+use queue::{room_key, Outbound, CLOSE_TIMEOUT, DEFAULT_CAPACITY};
+use surface::send_methods;
+
+/// a method of the room that removes a connection with given id, `Room::part` or `Room::evict`.
+pub(crate) type Removal<S, R, C, K> = fn(&mut Room<S, R, C>, &K) -> Option<Connection<S, C>>;
+
+/// a connection of a room. `S` is the type of the state of the connection, which is `()` by default, and `C` is the type of it's id, which is `String` by default.
 #[derive(Clone)]
-pub struct Connection {
-    pub id: String,
-    pub session: Session
+pub struct Connection<S = (), C = String> {
+    pub id: C,
+    pub session: Session,
+    /// attributes of the connection, which can be read in the conditions of `_if` and `_if_not` methods. Use `.update_metadata()` methods to change them after the connection is added to a room.
+    pub metadata: Metadata,
+    /// the user-defined state of the connection, which can be read in the conditions of `_if` and `_if_not` methods like the metadata. Use `.update_state()` methods to change it after the connection is added to a room.
+    pub state: S,
+    outbound: Arc<Outbound>,
+    liveness: Arc<Liveness>,
+    latency: Arc<Latency>,
+    /// the handshake request that the connection is added with, the join hooks get it when the connection joins another room.
+    handshake: Option<Arc<Handshake>>
 }
 
+/// a room of connections. `R` is the type of the id of the room and `C` is the type of the id's of it's connections, both of them are `String` by default.
 #[derive(Clone)]
-pub struct Room {
-    pub id: String,
-    pub connectors: Vec<Connection>
+pub struct Room<S = (), R = String, C = String> {
+    pub id: R,
+    /// the connections of the room by their id. Use the methods of the room to add or remove them, so the broadcaster can find the rooms of a connection without scanning all of them.
+    pub connectors: HashMap<C, Connection<S, C>>,
+    events: Listeners<R, C>,
+    index: Index<R, C>,
+    concurrency: Option<usize>,
+    queue_capacity: usize,
+    slow_consumer_policy: SlowConsumerPolicy,
+    duplicate_policy: DuplicatePolicy,
+    presence: bool,
+    fan_out: FanOut,
+    channel: Option<Channel>,
+    /// tells the subscription of the room apart in the queues of the connections that are in many rooms.
+    key: u64,
+    graveyard: Graveyard<C>
 }
 
+/// the id's of the connections of a room whose writer task is stopped, they're pruned by the next operation on the room.
+pub(crate) type Graveyard<C> = Arc<Mutex<Vec<C>>>;
+
 #[derive(Clone)]
-pub struct Broadcaster {
-    pub rooms: Vec<Room>
+pub struct Broadcaster<S = (), R = String, C = String> {
+    /// the rooms by their id.
+    pub rooms: HashMap<R, Room<S, R, C>>,
+    events: Listeners<R, C>,
+    index: Index<R, C>,
+    concurrency: Option<usize>,
+    queue_capacity: usize,
+    slow_consumer_policy: SlowConsumerPolicy,
+    duplicate_policy: DuplicatePolicy,
+    presence: bool,
+    fan_out: FanOut,
+    ids: Ids,
+    join_hooks: JoinHooks<S, R, C>
 }
 
 impl Connection {
-    /// creates a single connection.
+    /// creates a single connection and spawns the writer task of it, so it has to be called inside of an actix runtime. Messages sent from the connection are put in a queue that holds 64 messages at most and the writer task sends them to the session in order.
     pub fn create(id: String, session: Session) -> Self {
+        Self::with_capacity(id, session, DEFAULT_CAPACITY)
+    }
+
+    /// creates a single connection with an outbound queue that holds given number of messages at most.
+    pub fn with_capacity(id: String, session: Session, capacity: usize) -> Self {
+        Self::build(id, session, capacity, ())
+    }
+}
+
+impl<S, C: Id> Connection<S, C> {
+    /// creates a single connection with given state, like `Connection::create()`.
+    ///
+    /// ```rust,ignore
+    ///
+    /// struct User { id: u64, admin: bool }
+    ///
+    /// let mut connection = Connection::with_state(id, session, User { id: 42, admin: false });
+    ///
+    /// connection.send_if(msg, |conn| !conn.state.admin).await?;
+    ///
+    /// ```
+    pub fn with_state(id: C, session: Session, state: S) -> Self {
+        Self::build(id, session, DEFAULT_CAPACITY, state)
+    }
+
+    pub(crate) fn build(id: C, session: Session, capacity: usize, state: S) -> Self {
         Self {
-            id, 
+            id,
+            outbound: Arc::new(Outbound::spawn(session.clone(), capacity, SlowConsumerPolicy::default())),
+            liveness: Arc::new(Liveness::default()),
+            latency: Arc::new(Latency::default()),
+            handshake: None,
+            metadata: Metadata::default(),
+            state,
             session
         }
     }
 
-    /// sends message from single connection.
-    pub async fn send(&mut self, message: String) -> () {
-        self.session.text(message).await.unwrap();
+    /// sets what happens when the outbound queue of the connection is full, default is `SlowConsumerPolicy::DropNewest`. It's shared between the clones of the connection, so it can be changed from a connection that returned from `.check_connection()` method of `Room`.
+    ///
+    /// ```rust,ignore
+    ///
+    /// use actix_wsb::SlowConsumerPolicy;
+    ///
+    /// if let Some(connection) = broadcaster.write().unwrap().room(&room_id)?.check_connection(&id) {
+    ///     connection.set_slow_consumer_policy(SlowConsumerPolicy::DropOldest);
+    /// }
+    ///
+    /// ```
+    pub fn set_slow_consumer_policy(&self, policy: SlowConsumerPolicy) {
+        self.outbound.set_policy(policy);
+    }
+
+    /// sends message from single connection. The message is put in the outbound queue of the connection without waiting the client, it returns `Error::Closed` if the session is closed. If the client can't keep up, it applies the slow consumer policy of the connection.
+    pub async fn send(&mut self, message: impl Into<ByteString>) -> Result<(), Error> {
+        self.enqueue(Message::Text(message.into())).await
     }
 
     /// sends message from single connection if given condition is true.
-    pub async fn send_if<F>(&mut self, message: String, condition: F) where F: Fn(&Connection) -> bool {
-        if condition(&self) {
-            self.session.text(message).await.unwrap();
+    pub async fn send_if<F>(&mut self, message: impl Into<ByteString>, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if condition(self) {
+            self.enqueue(Message::Text(message.into())).await?;
         }
+
+        Ok(())
     }
 
-    /// */ sends message from single connection if given condition is false.
-    pub async fn send_if_not<F>(&mut self, message: String, condition: F) where F: Fn(&Connection) -> bool {
-        if !condition(&self) {
-            self.session.text(message).await.unwrap();
+    /// sends message from single connection if given condition is false.
+    pub async fn send_if_not<F>(&mut self, message: impl Into<ByteString>, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if !condition(self) {
+            self.enqueue(Message::Text(message.into())).await?;
         }
+
+        Ok(())
     }
 
     /// sends a ping message from single connection.
-    pub async fn ping(&mut self, bytes: &Vec<u8>) -> () {
-        self.session.ping(bytes).await.unwrap();
+    pub async fn ping(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.enqueue(Message::Ping(Bytes::copy_from_slice(bytes))).await
     }
 
     /// sends a ping message from single connection if given condition is true.
-    pub async fn ping_if<F>(&mut self, bytes: &Vec<u8>, condition: F) where F: Fn(&Connection) -> bool {
-        if condition(&self) {
-            self.session.ping(bytes).await.unwrap();
+    pub async fn ping_if<F>(&mut self, bytes: &[u8], condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if condition(self) {
+            self.enqueue(Message::Ping(Bytes::copy_from_slice(bytes))).await?;
         }
+
+        Ok(())
     }
 
-    /// */ sends a ping message from single connection if given condition is false.
-    pub async fn ping_if_not<F>(&mut self, bytes: &Vec<u8>, condition: F) where F: Fn(&Connection) -> bool {
-        if !condition(&self) {
-            self.session.ping(bytes).await.unwrap();
+    /// sends a ping message from single connection if given condition is false.
+    pub async fn ping_if_not<F>(&mut self, bytes: &[u8], condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if !condition(self) {
+            self.enqueue(Message::Ping(Bytes::copy_from_slice(bytes))).await?;
         }
+
+        Ok(())
     }
 
     /// sends a pong message from single connection.
-    pub async fn pong(&mut self, bytes: &Vec<u8>) -> () {
-        self.session.pong(bytes).await.unwrap();
+    pub async fn pong(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.enqueue(Message::Pong(Bytes::copy_from_slice(bytes))).await
     }
 
     /// sends a pong message from single connection if given condition is true.
-    pub async fn pong_if<F>(&mut self, bytes: &Vec<u8>, condition: F) where F: Fn(&Connection) -> bool {
-        if condition(&self) {
-            self.session.pong(bytes).await.unwrap();
+    pub async fn pong_if<F>(&mut self, bytes: &[u8], condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if condition(self) {
+            self.enqueue(Message::Pong(Bytes::copy_from_slice(bytes))).await?;
         }
+
+        Ok(())
     }
 
     /// sends a pong message from single connection if given condition is false.
-    pub async fn pong_if_not<F>(&mut self, bytes: &Vec<u8>, condition: F) where F: Fn(&Connection) -> bool {
-        if !condition(&self) {
-            self.session.pong(bytes).await.unwrap();
+    pub async fn pong_if_not<F>(&mut self, bytes: &[u8], condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if !condition(self) {
+            self.enqueue(Message::Pong(Bytes::copy_from_slice(bytes))).await?;
         }
+
+        Ok(())
     }
 
-    /// sends a pong message from single connection.
-    pub async fn binary(&mut self, bytes: Bytes) -> () {
-        self.session.binary(bytes).await.unwrap();
+    /// sends raw binary bytes from single connection.
+    pub async fn binary(&mut self, bytes: impl Into<Bytes>) -> Result<(), Error> {
+        self.enqueue(Message::Binary(bytes.into())).await
     }
 
-    /// sends a pong message from single connection if given condition is true.
-    pub async fn binary_if<F>(&mut self, bytes: Bytes, condition: F) where F: Fn(&Connection) -> bool {
-        if condition(&self) {
-            self.session.binary(bytes).await.unwrap();
+    /// sends raw binary bytes from single connection if given condition is true.
+    pub async fn binary_if<F>(&mut self, bytes: impl Into<Bytes>, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if condition(self) {
+            self.enqueue(Message::Binary(bytes.into())).await?;
         }
+
+        Ok(())
     }
 
-    /// sends a pong message from single connection if given condition is false.
-    pub async fn binary_if_not<F>(&mut self, bytes: Bytes, condition: F) where F: Fn(&Connection) -> bool {
-        if !condition(&self) {
-            self.session.binary(bytes).await.unwrap();
+    /// sends raw binary bytes from single connection if given condition is false.
+    pub async fn binary_if_not<F>(&mut self, bytes: impl Into<Bytes>, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if !condition(self) {
+            self.enqueue(Message::Binary(bytes.into())).await?;
         }
+
+        Ok(())
     }
 
     /// sends a continuation message from single connection with given type.
-    pub async fn continuation(&mut self, item: Item) -> () {
-        match item {
-            Item::FirstText(ref text) => {
-                let text = text;
-                self.session.continuation(Item::FirstText(text.clone())).await.unwrap()
-            },
-            Item::FirstBinary(ref binary) => {
-                let binary = binary;
-                self.session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-            },
-            Item::Continue(ref cont_msg) => {
-                let cont_msg = cont_msg;
-                self.session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-            },
-            Item::Last(ref last_msg) => {
-                let last_msg = last_msg;
-                self.session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-            }
-        }
+    pub async fn continuation(&mut self, item: Item) -> Result<(), Error> {
+        self.enqueue(Message::Continuation(item)).await
     }
 
     /// sends a continuation message from single connection with given type if given condition is true.
-    pub async fn continuation_if<F>(&mut self, item: Item, condition: F) where F: Fn(&Connection) -> bool { 
-        if condition(&self) { 
-            match item {
-                Item::FirstText(ref text) => {
-                    let text = text;
-                    self.session.continuation(Item::FirstText(text.clone())).await.unwrap()
-                },
-                Item::FirstBinary(ref binary) => {
-                    let binary = binary;
-                    self.session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-                },
-                Item::Continue(ref cont_msg) => {
-                    let cont_msg = cont_msg;
-                    self.session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-                },
-                Item::Last(ref last_msg) => {
-                    let last_msg = last_msg;
-                    self.session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-                }
-            }
-        } 
+    pub async fn continuation_if<F>(&mut self, item: Item, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if condition(self) {
+            self.enqueue(Message::Continuation(item)).await?;
+        }
+
+        Ok(())
     }
 
     /// sends a continuation message from single connection with given type if given condition is false.
-    pub async fn continuation_if_not<F>(&mut self, item: Item, condition: F) where F: Fn(&Connection) -> bool { 
-        if !condition(&self) { 
-            match item {
-                Item::FirstText(ref text) => {
-                    let text = text;
-                    self.session.continuation(Item::FirstText(text.clone())).await.unwrap()
-                },
-                Item::FirstBinary(ref binary) => {
-                    let binary = binary;
-                    self.session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-                },
-                Item::Continue(ref cont_msg) => {
-                    let cont_msg = cont_msg;
-                    self.session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-                },
-                Item::Last(ref last_msg) => {
-                    let last_msg = last_msg;
-                    self.session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-                }
-            }
-        } 
+    pub async fn continuation_if_not<F>(&mut self, item: Item, condition: F) -> Result<(), Error> where F: Fn(&Connection<S, C>) -> bool {
+        if !condition(self) {
+            self.enqueue(Message::Continuation(item)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// sends the close frame after the messages that waiting in the queue and waits until it's sent. The connection doesn't accept messages after that. If the client doesn't read them in 5 seconds, the writer task of the connection is aborted, so it's session is dropped without the close frame, and `Error::Timeout` is returned.
+    pub async fn close(&self, reason: Option<CloseReason>) -> Result<(), Error> {
+        self.outbound.close(reason).await
+    }
+
+    /// returns the count of the messages that waiting in the outbound queue of the connection.
+    pub fn queued(&self) -> usize {
+        self.outbound.len()
+    }
+
+    /// returns the count of the messages that dropped because the outbound queue of the connection was full.
+    pub fn dropped(&self) -> u64 {
+        self.outbound.dropped()
+    }
+
+    /// returns the count of the messages of a `FanOut::Channel` room that the connection missed, because it fell behind the channel.
+    pub fn lagged(&self) -> u64 {
+        self.outbound.lagged()
+    }
+
+    /// sends a timestamped ping from single connection to measure the round-trip time of it. When it's pong is recorded with `.record_pong()`, the round-trip time is added to the statistics of the connection. The pings of the heartbeat supervisor are also timestamped.
+    pub async fn ping_timed(&mut self) -> Result<(), Error> {
+        self.enqueue(Message::Ping(self.latency.stamp())).await
+    }
+
+    /// records that a pong with given payload is received from the client, so the heartbeat supervisor doesn't count the pings before it as missed. If it's the pong of a timestamped ping, it also records the round-trip time and returns true. Call it when the client sends a `Message::Pong`.
+    ///
+    /// ```rust,ignore
+    ///
+    /// Message::Pong(bytes) => {
+    ///     connection.record_pong(&bytes);
+    /// },
+    ///
+    /// ```
+    pub fn record_pong(&self, bytes: &[u8]) -> bool {
+        self.liveness.record_pong();
+        self.latency.record(bytes)
+    }
+
+    /// returns the round-trip time statistics of the connection, or `None` if none of the timestamped pings are answered yet.
+    pub fn rtt(&self) -> Option<RttStats> {
+        self.latency.stats()
+    }
+
+    /// returns the time that the last pong is received from the client. It's the creation time of the connection if it's never received.
+    pub fn last_pong(&self) -> Instant {
+        self.liveness.last_pong()
+    }
+
+    /// returns how many pings of the heartbeat supervisor in a row are sent to the connection without a pong.
+    pub fn missed_heartbeats(&self) -> u32 {
+        self.liveness.missed()
+    }
+
+    /// checks if both of the connections are the clones of the same connection.
+    pub(crate) fn same(&self, other: &Connection<S, C>) -> bool {
+        Arc::ptr_eq(&self.outbound, &other.outbound)
+    }
+
+    /// puts the message in the outbound queue, the writer task sends it to the session.
+    pub(crate) async fn enqueue(&self, message: Message) -> Result<(), Error> {
+        self.outbound.push(message).await
     }
 }
 
 impl Room {
-    /// checks if a connection with given id exist and if it's not add a connection with given id and Session to a room.
-    pub fn add_connection(&mut self, id: &String, session: Session) {
-        let check_is_connection_exist = self.connectors.iter().any(|room| room.id == *id);
-
-        match check_is_connection_exist {
-            true => (),
-            false => {
-                let connection = Connection {
-                    id: id.clone(),
-                    session
-                };
+    /// creates an empty room. Rooms that created with that function don't emit events to the listeners of a broadcaster, use `.handle_room()` method of `Broadcaster` instead if you need them.
+    pub fn create(id: String) -> Self {
+        Self::typed(id)
+    }
+}
 
-                self.connectors.push(connection);
-            }
+impl<S: Clone, R: Id, C: Id> Room<S, R, C> {
+    /// creates an empty room whose connections have a state of type `S`, like `Room::create()`.
+    ///
+    /// ```rust,ignore
+    ///
+    /// let room = Room::<User>::typed(room_id);
+    ///
+    /// ```
+    pub fn typed(id: R) -> Self {
+        Self {
+            id,
+            connectors: HashMap::new(),
+            events: Listeners::default(),
+            index: Index::default(),
+            concurrency: None,
+            queue_capacity: DEFAULT_CAPACITY,
+            slow_consumer_policy: SlowConsumerPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            presence: false,
+            fan_out: FanOut::Direct,
+            channel: None,
+            key: room_key(),
+            graveyard: Graveyard::default()
         }
     }
 
-    /// removes if a connection with given id exist.
-    pub fn remove_connection(&mut self, id: String) {
-        self.connectors.retain(|connection| { 
-            if connection.id == id { 
-                false 
-            } else { 
-                true 
-            }
-        });
+    /// sets how many connections of the room can be sent to at the same time. `None` means there is no limit, which is the default. Limit of zero is treated as one.
+    pub fn set_concurrency(&mut self, limit: Option<usize>) {
+        self.concurrency = limit;
     }
 
-    /// checks if a connection exist and returns it as an option.
-    pub fn check_connection(&mut self, id: &String) -> Option<Connection> {
-        let connection = self.connectors.iter().find(|room| room.id == *id);
-
-        match connection {
-            Some(connection) => Some(connection.clone()),
-            None => None
-        }
-    }
-
-    /// broadcastes the message to all room connectors.
-    pub async fn broadcast(&mut self, message: String) {
-        for connection in &mut self.connectors { 
-            let message = message.clone(); 
-            let session = &mut connection.session; 
-            
-            let _ = session.text(message).await;
-        }
-    }
-
-    /// broadcastes the message if given condition for connection instances is true.
-    pub async fn broadcast_if<F>(&mut self, message: String, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if condition(connection) { 
-                let message = message.clone(); 
-                let session = &mut connection.session; 
-                let _ = session.text(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the message if given condition for connection instances is false.
-    pub async fn broadcast_if_not<F>(&mut self, message: String, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if !condition(connection) { 
-                let message = message.clone(); 
-                let session = &mut connection.session; 
-                let _ = session.text(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the ping to all room connectors.
-    pub async fn ping(&mut self, bytes: Vec<u8>) { 
-        for connection in &mut self.connectors { 
-            let message = &bytes; 
-            let session = &mut connection.session; 
-                
-            let _ = session.ping(message).await;
-        }
-    }
-
-    /// broadcastes the ping if given condition for connection instances is true.
-    pub async fn ping_if<F>(&mut self, bytes: Vec<u8>, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if condition(connection) { 
-                let message = &bytes; 
-                let session = &mut connection.session; 
-                let _ = session.ping(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the ping if given condition for connection instances is false.
-    pub async fn ping_if_not<F>(&mut self, bytes: Vec<u8>, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if !condition(connection) { 
-                let message = &bytes; 
-                let session = &mut connection.session; 
-                let _ = session.ping(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the pong to all room connectors.
-    pub async fn pong(&mut self, bytes: Vec<u8>) { 
-        for connection in &mut self.connectors { 
-            let message = &bytes; 
-            let session = &mut connection.session; 
-                
-            let _ = session.pong(message).await;
-        }
-    }
-
-    /// broadcastes the pong if given condition for connection instances is true.
-    pub async fn pong_if<F>(&mut self, bytes: Vec<u8>, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if condition(connection) { 
-                let message = &bytes; 
-                let session = &mut connection.session; 
-                let _ = session.pong(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the pong if given condition for connection instances is false.
-    pub async fn pong_if_not<F>(&mut self, bytes: Vec<u8>, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if !condition(connection) { 
-                let message = &bytes; 
-                let session = &mut connection.session; 
-                let _ = session.pong(message).await;
-            } 
-        } 
-    }
-
-    /// Broadcastes the raw binary bytes to all room connectors.
-    pub async fn binary(&mut self, bytes: Bytes) { 
-        for connection in &mut self.connectors { 
-            let message = bytes.clone();
-            let session = &mut connection.session; 
-                
-            let _ = session.binary(message).await;
-        }
-    }
-
-    /// broadcastes the raw binary bytes if given condition for connection instances is true.
-    pub async fn binary_if<F>(&mut self, bytes: Bytes, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if condition(connection) { 
-                let message = bytes.clone(); 
-                let session = &mut connection.session; 
-                let _ = session.binary(message).await;
-            } 
-        } 
-    }
-
-    /// broadcastes the raw binary bytes if given condition for connection instances is false.
-    pub async fn binary_if_not<F>(&mut self, bytes: Bytes, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if !condition(connection) { 
-                let message = bytes.clone(); 
-                let session = &mut connection.session; 
-                let _ = session.binary(message).await;
-            } 
-        } 
-    }
-
-    /// Broadcastes the continuation message to all room connectors.
-    pub async fn continuation(&mut self, item: Item) { 
-        for connection in &mut self.connectors { 
-            let session = &mut connection.session; 
-
-            match item {
-                Item::FirstText(ref text) => {
-                    let text = text;
-                    session.continuation(Item::FirstText(text.clone())).await.unwrap()
-                },
-                Item::FirstBinary(ref binary) => {
-                    let binary = binary;
-                    session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-                },
-                Item::Continue(ref cont_msg) => {
-                    let cont_msg = cont_msg;
-                    session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-                },
-                Item::Last(ref last_msg) => {
-                    let last_msg = last_msg;
-                    session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-                }
-            }
-        }
+    /// sets how many messages the outbound queue of the connections that will be added to the room can hold, default is 64.
+    pub fn set_queue_capacity(&mut self, capacity: usize) {
+        self.queue_capacity = capacity;
     }
 
-    /// broadcastes the continuation messages if given condition for connection instances is true.
-    pub async fn continuation_if<F>(&mut self, item: Item, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if condition(connection) { 
-                let session = &mut connection.session; 
-
-                match item {
-                    Item::FirstText(ref text) => {
-                        let text = text;
-                        session.continuation(Item::FirstText(text.clone())).await.unwrap()
-                    },
-                    Item::FirstBinary(ref binary) => {
-                        let binary = binary;
-                        session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-                    },
-                    Item::Continue(ref cont_msg) => {
-                        let cont_msg = cont_msg;
-                        session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-                    },
-                    Item::Last(ref last_msg) => {
-                        let last_msg = last_msg;
-                        session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-                    }
-                }
-            } 
-        } 
-    }
-
-    /// broadcastes the continuation messages if given condition for connection instances is false.
-    pub async fn continuation_if_not<F>(&mut self, item: Item, condition: F) where F: Fn(&Connection) -> bool { 
-        for connection in &mut self.connectors { 
-            if !condition(connection) { 
-                let session = &mut connection.session; 
-    
-                match item {
-                    Item::FirstText(ref text) => {
-                        let text = text;
-                        session.continuation(Item::FirstText(text.clone())).await.unwrap()
-                    },
-                    Item::FirstBinary(ref binary) => {
-                        let binary = binary;
-                        session.continuation(Item::FirstBinary(binary.clone())).await.unwrap()
-                    },
-                    Item::Continue(ref cont_msg) => {
-                        let cont_msg = cont_msg;
-                        session.continuation(Item::Continue(cont_msg.clone())).await.unwrap()
-                    },
-                    Item::Last(ref last_msg) => {
-                        let last_msg = last_msg;
-                        session.continuation(Item::Last(last_msg.clone())).await.unwrap()
-                    }
-                }
-            } 
-        } 
+    /// sets the slow consumer policy of the connections that will be added to the room, default is `SlowConsumerPolicy::DropNewest`.
+    pub fn set_slow_consumer_policy(&mut self, policy: SlowConsumerPolicy) {
+        self.slow_consumer_policy = policy;
     }
 
-    /// closes the connection with given id and removes it from it's room. This is the convenient way of closing a connection.
-    /// 
-    /// ```rust
-    /// 
-    /// Message::Close(reason) => {
-    ///     let _ = get_broadcaster.write().unwrap().room(&room_id).close_conn(reason, &id).await;
-    /// 
-    ///     break;
-    /// },
-    /// 
+    /// sets what happens when a session is added with the id of a connection that's already in the room, default is closing the existing connection and adding the new session instead.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// enables or disables the presence of the room, it's disabled by default. When it's enabled, a connection that joins the room gets the presence list of the room, the id and the metadata of every connection in it, and the other connections get a delta when a connection joins, leaves or it's metadata is updated. They're json text messages:
+    ///
+    /// ```text
+    ///
+    /// {"type":"presence_state","members":[{"id":"a","metadata":{"user_id":"42","roles":["admin"],"locale":null,"tags":{}}}]}
+    /// {"type":"presence_join","id":"b","metadata":{"user_id":null,"roles":[],"locale":"tr-TR","tags":{}}}
+    /// {"type":"presence_update","id":"b","metadata":{"user_id":null,"roles":[],"locale":"de-DE","tags":{}}}
+    /// {"type":"presence_leave","id":"b"}
+    ///
     /// ```
-    pub async fn close_conn(&mut self, reason: Option<CloseReason>, id: &String) {
-        self.connectors.retain(|conn| {
-            if conn.id == *id {
-                let reason = reason.clone();
-                    
-                let _ = async {
-                    let _ = conn.session.clone().close(reason).await;
-                };
-        
-                false
-            } else {
-                true
+    ///
+    /// The id's are strings whatever their type is. A leave is sent however the connection is removed, by a disconnect, a close, a move, the heartbeat or a failed send. The presence messages can use a few slots beyond the capacity of the outbound queues, so a burst of joins and leaves reaches a client that's a bit behind, the slow consumer policy applies to them when those are full too. The connections that are in the room already get the presence list when it's enabled. A session that's attached to a connection by `DuplicatePolicy::Allow` gets the presence list before the other messages.
+    pub fn set_presence(&mut self, enabled: bool) {
+        let enabling = enabled && !self.presence;
+        self.presence = enabled;
+
+        if let Some(state) = self.presence_state().filter(|_| enabling) {
+            for connection in self.connectors.values() {
+                let _ = connection.outbound.push_presence(clone_message(&state));
             }
-        });
+        }
     }
 
-    /// closes all the connections and entire room. Warning: it closes all connections but keeps room open, if you want to close all the connections directly, use the `.remove_room()` method of the Broadcaster struct instead.
-    pub async fn close(&mut self, reason: Option<CloseReason>) {
-        self.connectors.retain(|conn| {
-            let reason = reason.clone();
-                
-            let _ = async {
-                let _ = conn.session.clone().close(reason).await;
-            };
+    /// returns the presence list of the room, the id and the metadata of every connection of it, whether the presence is enabled or not.
+    pub fn presence(&self) -> Vec<(C, Metadata)> {
+        self.connectors.values().map(|connection| (connection.id.clone(), connection.metadata.clone())).collect()
+    }
 
-            false
-        });
+    /// adds a connection with given id and Session to the room. If there is a connection with that id already, the duplicate policy of the room decides what happens, check out `DuplicatePolicy`, and `Error::Duplicate` is returned if the session is rejected. It spawns the writer task of the connection, so it has to be called inside of an actix runtime.
+    pub fn add_connection<K>(&mut self, id: &K, session: Session) -> Result<(), Error> where S: Default, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.insert_connection(id, session, Metadata::default(), S::default(), None)
     }
-    
-    /// closes the connection and removes it from room if given condition for connection instances is true. Room still stay open.
-    pub async fn close_if<F>(&mut self, reason: Option<CloseReason>, condition: F) where F: Fn(&Connection) -> bool { 
-        self.connectors.retain(|connection| {
-            if condition(connection) {
-                let reason = reason.clone();
-                
-                let _ = async {
-                    let _ = connection.session.clone().close(reason).await;
-                };
-    
-                false
-            } else {
-                true
-            }
-        });
+
+    /// same as `.add_connection()`, but the connection has given metadata.
+    pub fn add_connection_with<K>(&mut self, id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.insert_connection(id, session, metadata, S::default(), None)
     }
-    
-    /// closes the connection and removes it from room if given condition for connection instances is false. Room still stay open.
-    pub async fn close_if_not<F>(&mut self, reason: Option<CloseReason>, condition: F) where F: Fn(&Connection) -> bool { 
-        self.connectors.retain(|connection| {
-            if !condition(connection) {
-                let reason = reason.clone();
-                
-                let _ = async {
-                    let _ = connection.session.clone().close(reason).await;
-                };
-    
-                false
-            } else {
-                true
+
+    /// same as `.add_connection()`, but the connection has given state.
+    pub fn add_connection_with_state<K>(&mut self, id: &K, session: Session, state: S) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.insert_connection(id, session, Metadata::default(), state, None)
+    }
+
+    pub(crate) fn insert_connection<K>(&mut self, id: &K, session: Session, metadata: Metadata, state: S, handshake: Option<&Handshake>) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.reap();
+
+        let session = match self.connectors.get(id) {
+            None => session,
+            Some(existing) => match self.duplicate_policy.clone() {
+                DuplicatePolicy::Reject(reason) => {
+                    actix_web::rt::spawn(session.close(reason));
+
+                    return Err(Error::Duplicate(id.to_string()));
+                },
+                DuplicatePolicy::Replace(reason) => {
+                    if let Some(replaced) = self.evict(id) {
+                        replaced.outbound.abort(reason);
+                    }
+
+                    session
+                },
+                DuplicatePolicy::Allow => match existing.outbound.attach(session, self.presence_state()) {
+                    Ok(()) => return Ok(()),
+                    // the existing connection is closed already, so the new session takes it's place.
+                    Err(session) => {
+                        self.prune(&[id.to_owned()]);
+
+                        session
+                    }
+                }
             }
-        });
+        };
+
+        let mut connection = Connection::build(id.to_owned(), session, self.queue_capacity, state);
+        connection.metadata = metadata;
+        connection.handshake = handshake.cloned().map(Arc::new);
+        connection.set_slow_consumer_policy(self.slow_consumer_policy.clone());
+
+        self.subscribe(&connection);
+        self.watch(&connection);
+        self.index.insert(&connection.id, &self.id);
+        self.connectors.insert(id.to_owned(), connection);
+        self.events.emit(Event::Joined { room: self.id.clone(), connection: id.to_owned() });
+        self.present(&id.to_owned());
+
+        Ok(())
     }
-}
 
-impl Broadcaster {
-    /// create a new broadcaster instance. 
-    pub fn new() -> Arc<RwLock<Self>> { 
-        Arc::new(RwLock::new(Self::default())) 
+    /// adds a clone of given connection to the room as another membership of it, if there is no connection with that id in the room. The memberships share the session, the outbound queue, the liveness and the round-trip time statistics of the connection, so it's pinged once per heartbeat and closing it in a room closes it in all of them.
+    pub(crate) fn join_copy(&mut self, connection: &Connection<S, C>) {
+        self.adopt(connection.clone());
     }
 
-    /// does all the setup basically. You don't have to use other functions for all the grouping of rooms and connections. You can give the same room id for all instances if you don't want to seperate communication groups. But you have to give different connection id's to each session, otherwise it'll introduce bugs.
-    ///     let id = query.id.as_ref().unwrap().to_string();
-    /// 
-    ///```rust
-    /// 
-    /// let id = query.id.as_ref().unwrap().to_string();
-    /// let room_id = query.room.as_ref().unwrap().to_string();
-    ///
-    /// let get_broadcaster = Broadcaster::handle(&broadcaster, &room_id, &id, session);
-    /// 
-    ///```
-    pub fn handle(broadcaster: &Arc<RwLock<Self>>, room_id: &String, conn_id: &String, session: Session) -> Arc<RwLock<Self>> {
-        let mut broadcaster_write = broadcaster.write().unwrap();
+    /// adds a connection that's taken from another room with it's outbound queue, so the messages that waiting in it are still sent. Returns false if there is a connection with that id in the room already.
+    pub(crate) fn adopt(&mut self, connection: Connection<S, C>) -> bool {
+        self.reap();
+
+        if self.connectors.contains_key(&connection.id) {
+            return false;
+        }
+
+        let id = connection.id.clone();
 
-        broadcaster_write.handle_room(room_id).add_connection(conn_id, session);
+        self.subscribe(&connection);
+        self.watch(&connection);
+        self.index.insert(&connection.id, &self.id);
+        self.connectors.insert(id.clone(), connection);
+        self.events.emit(Event::Joined { room: self.id.clone(), connection: id.clone() });
+        self.present(&id);
 
-        Arc::clone(&broadcaster)
+        true
     }
-    
-    /// this function check if a room exist and if it's exist returns it, if it's not then creates it. If you just want to check if a room exist, use .check() instead.
-    /// 
-    ///```rust
-    /// 
-    /// let mut broadcaster_write = broadcaster.write().unwrap();
-    /// 
-    /// let room_id = "1".to_string();
-    ///
-    /// broadcaster_write.handle_room(&room_id)
-    /// 
-    ///```
-    /// 
-    pub fn handle_room(&mut self, id: &String) -> &mut Room {
-        if let Some(index) = self.rooms.iter().position(|room| room.id == *id) {
-            return &mut self.rooms[index];
-        }
-    
-        self.rooms.push(Room {
-            id: id.clone(),
-            connectors: vec![],
-        });
-    
-        self.rooms.last_mut().unwrap()
+
+    /// runs the join hooks for the connection that's going to be added by a `.handle()` method and adds it if none of them rejects it. Otherwise the session is closed with the reason of the hook and `Error::Rejected` is returned.
+    pub(crate) fn admit<K>(&mut self, hooks: &JoinHooks<S, R, C>, id: &K, session: Session, metadata: Metadata, state: S, handshake: Option<&Handshake>) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let request = JoinRequest { room: &self.id, connection: &id.to_owned(), metadata: &metadata, state: &state, members: self.connectors.len(), handshake };
+
+        hooks.check(&request, &session)?;
+
+        self.insert_connection(id, session, metadata, state, handshake)
     }
 
-    /// it scans a room with given id and it returns it if it's exist. if there is a risk that room isn't exist than use ".check_room()"
-    pub fn room(&mut self, id: &String) -> &mut Room {
-        return self.rooms.iter_mut().find(|room| room.id == *id).unwrap();
+    /// runs the join hooks for a connection that's going to be added by `.join()` or `.move_connection()`, with it's metadata, state and handshake request. Unlike `.admit()` it doesn't close the session if one of them rejects it, because the connection stays in it's other rooms. It does nothing if the connection is in the room already.
+    pub(crate) fn welcome(&self, hooks: &JoinHooks<S, R, C>, connection: &Connection<S, C>) -> Result<(), Error> {
+        if self.connectors.contains_key(&connection.id) {
+            return Ok(());
+        }
+
+        hooks.verify(&JoinRequest { room: &self.id, connection: &connection.id, metadata: &connection.metadata, state: &connection.state, members: self.connectors.len(), handshake: connection.handshake.as_deref() })
     }
 
-    /// checks a room and if it's exist, returns a mutable reference of that room.
-    pub fn check_room(&mut self, id: &String) -> Option<&mut Room> {
-        match self.rooms.iter_mut().find(|room| room.id == *id) {
-            Some(room) => Some(room),
-            None => None
+    /// removes the connection with given id, returns `Error::ConnectionNotFound` if there is no connection with that id.
+    pub fn remove_connection<K>(&mut self, id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.part(id) {
+            Some(_) => Ok(()),
+            None => Err(Error::ConnectionNotFound(id.to_string()))
         }
     }
 
-    /// it returns room if exist with given ip. Use .handle_room() method if you want to create a room with given id.
-    pub fn check(&self, id: &String) -> bool {
-        return self.rooms.iter().any(|room| room.id == *id);
+    /// changes the metadata of the connection with given id, returns `Error::ConnectionNotFound` if there is no connection with that id.
+    ///
+    /// ```rust,ignore
+    ///
+    /// broadcaster.write().unwrap().room(&room_id)?.update_metadata(&id, |metadata| {
+    ///     metadata.roles.insert("moderator".to_string());
+    /// })?;
+    ///
+    /// ```
+    pub fn update_metadata<F, K>(&mut self, id: &K, f: F) -> Result<(), Error> where F: FnOnce(&mut Metadata), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.connectors.get_mut(id) {
+            Some(connection) => {
+                f(&mut connection.metadata);
+
+                if self.presence {
+                    let update = presence::updated(&connection.id, &connection.metadata);
+                    let id = connection.id.clone();
+
+                    self.announce(&update, Some(&id));
+                }
+
+                Ok(())
+            },
+            None => Err(Error::ConnectionNotFound(id.to_string()))
+        }
     }
 
-    /// iterates through every room and does something with them immutably. You cannot mutate anything inside of it, even rooms and not captured variables.      
-    /// 
-    /// ```rust
-    /// 
-    /// use actix_wsb::Broadcaster;
-    /// 
-    /// fn main () {
-    ///     let broadcaster = Broadcaster::new();
-    /// 
-    ///     broadcaster.read().unwrap().each_room_immut(|room| println!("hello, {}. guest!", room.id));
-    /// }
-    /// 
-    /// 
+    /// changes the state of the connection with given id, returns `Error::ConnectionNotFound` if there is no connection with that id.
+    ///
+    /// ```rust,ignore
+    ///
+    /// broadcaster.write().unwrap().room(&room_id)?.update_state(&id, |user| user.admin = true)?;
+    ///
     /// ```
-    pub fn each_room_immut<F>(&self, f: F) where F: Fn(&Room) {
-        for room in &self.rooms {
-            f(room);
+    pub fn update_state<F, K>(&mut self, id: &K, f: F) -> Result<(), Error> where F: FnOnce(&mut S), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.connectors.get_mut(id) {
+            Some(connection) => {
+                f(&mut connection.state);
+
+                Ok(())
+            },
+            None => Err(Error::ConnectionNotFound(id.to_string()))
         }
     }
-    
-    /// iterates through every room and does something with them immutably. You cannot mutate rooms itself but can mutate captured variables.
-    /// 
-    /// ```rust
-    /// 
-    /// use actix_wsb::Broadcaster;
-    /// 
-    /// fn main () {
-    ///     let broadcaster = Broadcaster::new();
-    /// 
-    ///     let mut num = 0;
-    /// 
-    ///     broadcaster.read().unwrap().each_room(|room| {
-    ///         num = num + 1;
-    ///     });
-    /// 
-    ///     println!("here is number: {}", num)
+
+    /// checks if a connection exist and returns it as an option.
+    pub fn check_connection<K>(&mut self, id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.connectors.get(id).cloned()
+    }
+
+    /// returns the round-trip time statistics of all the connections of the room together, or `None` if none of them is measured yet. `last` is the latest measurement of any connection.
+    ///
+    /// ```rust,ignore
+    ///
+    /// if let Some(rtt) = broadcaster.write().unwrap().room(&room_id)?.rtt() {
+    ///     println!("p99 of the room is {:?}", rtt.p99);
     /// }
-    /// 
-    /// 
+    ///
     /// ```
-    pub fn each_room<F>(&self, mut f: F) where F: FnMut(&Room) {
-        for room in &self.rooms {
-            f(room);
-        }
+    pub fn rtt(&self) -> Option<RttStats> {
+        Latency::aggregate(self.connectors.values().map(|connection| connection.latency.as_ref()))
     }
 
-    /// iterates through every room and does something with them mutably. You can mutate everything belong to it. But warning, for now, you cannot send messages to client from it right now and until async closures will be stable probably we're not be able to do it. Because of that, we're not able to give examples for that.
-    pub async fn each_room_mut<F>(&mut self, mut f: F) where F: FnMut(&mut Room) {
-        for room in &mut self.rooms {
-            f(room);
-        }
+    send_methods! {
+        receiver: [mut],
+        room: [],
+        generics: [],
+        bounds: [],
+        condition: [],
+        output: DeliveryReport<C>,
+        close: [] Closing<DeliveryReport<C>>,
+        ///
+        /// Message will be sent to every connector even if some of them fails, it returns a `DeliveryReport` that tells which connectors the message is queued for and which of them failed. Connectors whose session is closed are removed from the room automatically.
     }
 
-    /// it removes a room with given id.
-    /// 
-    /// 
-    /// ```rust 
+    /// removes the connection with given id from it's room and returns the `Closing` that closes it when it's awaited, so the lock of the broadcaster can be released before that. This is the convenient way of closing a connection. It returns `Error::ConnectionNotFound` if there is no connection with that id.
+    ///
+    /// ```rust,ignore
+    ///
     /// Message::Close(reason) => {
-    ///     // warning, that closes and removes all the connections but not removes the room: 
-    ///     //let _ = get_broadcaster.write().unwrap().room(room_id.clone()).close(reason).await;
-    ///                
-    ///     // if you want to remove a room with removing all the connections, use this instead:
-    ///     // let _ = get_broadcaster.write().unwrap().remove_room(room_id.clone()).await;
+    ///     let closing = get_broadcaster.write().unwrap().room(&room_id)?.close_conn(reason, &id);
+    ///     let _ = closing.await;
     ///
-    ///     let _ = get_broadcaster.write().unwrap()
-    ///                                    .room(&room_id)
-    ///                                    .remove_room(reason, |conn| conn.id == *id).await;
-    ///                
     ///     break;
-    ///  },
-    /// ```
+    /// },
     ///
-    pub async fn remove_room(&mut self, id: String) {
-        self.rooms.retain(|room| { 
-            if room.id == id { 
-                let _ = async {
-                    room.clone().close(None).await;
-                };
-                
-                false 
-            } else { 
-                true 
-            }
-        });
+    /// ```
+    pub fn close_conn<K>(&mut self, reason: Option<CloseReason>, id: &K) -> Closing<Result<(), Error>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.evict(id) {
+            Some(connection) => {
+                let outbound = connection.outbound;
+
+                Closing::new(async move { outbound.close(reason).await })
+            },
+            None => Closing::ready(Err(Error::ConnectionNotFound(id.to_string())))
+        }
     }
 
-    /// it removes all empty rooms.
-    pub fn remove_empty_rooms(&mut self) {
-        self.rooms.retain(|room| { 
-            if room.connectors.is_empty() { 
-                false 
-            } else { 
-                true 
+    /// publishes the message to the channel of the room if it has one and prunes the connectors whose writer task is stopped, otherwise sends it to every connector.
+    pub(crate) async fn send_all(&mut self, message: Message) -> DeliveryReport<C> {
+        match self.publish(message) {
+            Ok(mut report) => {
+                report.pruned = self.reap();
+
+                report
+            },
+            Err(message) => self.dispatch(message, |_| true).await
+        }
+    }
+
+    /// sends the message to every connector that satisfies the condition and reports the result for each of them. The connectors whose session is closed are pruned from the room.
+    async fn dispatch<F>(&mut self, message: Message, condition: F) -> DeliveryReport<C> where F: Fn(&Connection<S, C>) -> bool {
+        let (mut targets, skipped) = self.select(condition);
+        let mut report = DeliveryReport::<C> { skipped, ..Default::default() };
+
+        fan_out(&mut targets, &message, self.concurrency, &mut report).await;
+
+        self.settle(&mut report);
+
+        report
+    }
+
+    /// removes the connectors that satisfies the condition from room and returns the `Closing` that sends their close frames concurrently.
+    pub(crate) fn close_where<F>(&mut self, reason: Option<CloseReason>, condition: F) -> Closing<DeliveryReport<C>> where F: Fn(&Connection<S, C>) -> bool {
+        let pruned = self.reap();
+        let (selected, skipped) = self.select(condition);
+        let mut closing = Vec::with_capacity(selected.len());
+
+        for connection in selected {
+            self.retire(&connection);
+            closing.push((connection.id, connection.outbound));
+        }
+
+        let limit = self.concurrency;
+
+        Closing::new(async move {
+            let mut report = DeliveryReport::<C> { skipped, pruned, ..Default::default() };
+
+            close_connections(&closing, reason, limit, &mut report).await;
+
+            report
+        })
+    }
+
+    /// copies the connectors that satisfies the condition and collects the id's of the ones that don't.
+    pub(crate) fn select<F>(&self, condition: F) -> (Vec<Connection<S, C>>, Vec<C>) where F: Fn(&Connection<S, C>) -> bool {
+        let mut selected = vec![];
+        let mut skipped = vec![];
+
+        for connection in self.connectors.values() {
+            if condition(connection) {
+                selected.push(connection.clone());
+            } else {
+                skipped.push(connection.id.clone());
             }
-        });
+        }
+
+        (selected, skipped)
     }
 
-    /// it removes a connection and returns the session struct of it. since async closures not stable yet, we cannot close the actual "Session" implementation in that method. For making that cleanup, we have to get actual Session implementation and close that connection manually - check out the example and readme.
-    /// This is the old way of removing connections. It'll not be removed but we don't recommend to use it unless you don't used it yet.
-    pub fn remove_connection(&mut self, id: String) -> Option<Session> {
-        for room in &mut self.rooms {
-            if let Some(pos) = room.connectors.iter().position(|connection| connection.id == id) {
-                let connection = room.connectors.remove(pos);
-                
-                return Some(connection.session);
+    /// removes the connectors with given id's and emits an `Event::Pruned` for each of them.
+    pub(crate) fn prune(&mut self, ids: &[C]) {
+        for id in ids {
+            if self.take(id).is_some() {
+                self.events.emit(Event::Pruned { room: self.id.clone(), connection: id.clone() });
             }
         }
-        None
     }
-} 
 
-impl Default for Broadcaster { 
-    fn default() -> Self { 
-        Self { 
-            rooms: vec![], 
-        } 
+    /// prunes the connectors that failed in the report and the ones whose writer task is stopped, and adds the latter to the `pruned` of the report.
+    pub(crate) fn settle(&mut self, report: &mut DeliveryReport<C>) {
+        self.prune(&report.pruned);
+
+        let reaped = self.reap();
+        report.pruned.extend(reaped);
     }
-}
\ No newline at end of file
+
+    /// prunes the connectors whose writer task is stopped since the last operation on the room, because they're closed in another room or their session is gone, and returns their id's.
+    pub(crate) fn reap(&mut self) -> Vec<C> {
+        let buried = std::mem::take(&mut *self.graveyard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        let mut reaped = vec![];
+
+        for id in buried {
+            // the id could be taken by another connection after the stopped one is removed, it's pruned only if it's stopped too.
+            let stopped = self.connectors.get(&id).is_some_and(|connection| connection.outbound.is_finished());
+
+            if stopped && self.take(&id).is_some() {
+                self.events.emit(Event::Pruned { room: self.id.clone(), connection: id.clone() });
+                reaped.push(id);
+            }
+        }
+
+        reaped
+    }
+
+    /// returns true if there are connectors whose writer task is stopped and they're waiting to be pruned.
+    pub(crate) fn has_dead(&self) -> bool {
+        !self.graveyard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_empty()
+    }
+
+    /// tells the room when the writer task of the connection stops, so it's pruned from the room even if the room doesn't send anything to it's queue.
+    fn watch(&self, connection: &Connection<S, C>) {
+        let graveyard = self.graveyard.clone();
+        let id = connection.id.clone();
+
+        connection.outbound.watch(self.key, Box::new(move || graveyard.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(id)));
+    }
+
+    /// removes the connection with given id from the room and the index.
+    fn take<K>(&mut self, id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.connectors.remove(id)?;
+
+        connection.outbound.unsubscribe(self.key);
+        connection.outbound.unwatch(self.key);
+        self.index.remove(&connection.id, &self.id);
+
+        if self.presence {
+            self.announce(&presence::left(&connection.id), None);
+        }
+
+        Some(connection)
+    }
+
+    /// sends the presence list to the connection with given id that just joined and a join delta to the others, if presence is enabled.
+    fn present(&self, id: &C) {
+        if !self.presence {
+            return;
+        }
+
+        if let Some(connection) = self.connectors.get(id) {
+            if let Some(state) = self.presence_state() {
+                let _ = connection.outbound.push_presence(state);
+            }
+
+            self.announce(&presence::joined(id, &connection.metadata), Some(id));
+        }
+    }
+
+    /// the presence list of the room as a message if presence is enabled, it's sent to the connections and the sessions that just joined.
+    fn presence_state(&self) -> Option<Message> {
+        if !self.presence {
+            return None;
+        }
+
+        let members = self.connectors.values().map(|member| (&member.id, &member.metadata));
+
+        Some(Message::Text(presence::state(members).into()))
+    }
+
+    /// sends the presence message to every connection of the room except given one.
+    fn announce(&self, message: &str, except: Option<&C>) {
+        let message = ByteString::from(message);
+
+        for connection in self.connectors.values() {
+            if except != Some(&connection.id) {
+                let _ = connection.outbound.push_presence(Message::Text(message.clone()));
+            }
+        }
+    }
+
+    /// removes the connection with given id without closing it and emits an `Event::Left` for it.
+    pub(crate) fn part<K>(&mut self, id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.take(id)?;
+        self.events.emit(Event::Left { room: self.id.clone(), connection: connection.id.clone() });
+
+        Some(connection)
+    }
+
+    /// removes the connection with given id that's going to be closed by the caller and emits an `Event::Closed` for it.
+    pub(crate) fn evict<K>(&mut self, id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.take(id)?;
+        self.events.emit(Event::Closed { room: self.id.clone(), connection: connection.id.clone() });
+
+        Some(connection)
+    }
+
+    /// removes the connection that's copied out of the room before with `.detach()` and emits an `Event::Closed` for it if it's removed.
+    pub(crate) fn retire(&mut self, connection: &Connection<S, C>) {
+        if self.detach(connection) {
+            self.events.emit(Event::Closed { room: self.id.clone(), connection: connection.id.clone() });
+        }
+    }
+
+    /// removes the connection from the room only if it's still the same connection, not another one that added with the same id after it's copied.
+    pub(crate) fn detach(&mut self, connection: &Connection<S, C>) -> bool {
+        match self.connectors.get(&connection.id) {
+            Some(current) if current.same(connection) => self.take(&connection.id).is_some(),
+            _ => false
+        }
+    }
+}
+
+impl<S, R, C> Default for Broadcaster<S, R, C> {
+    fn default() -> Self {
+        Self {
+            rooms: HashMap::new(),
+            events: Listeners::default(),
+            index: Index::default(),
+            concurrency: None,
+            queue_capacity: DEFAULT_CAPACITY,
+            slow_consumer_policy: SlowConsumerPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            presence: false,
+            fan_out: FanOut::Direct,
+            ids: Ids::default(),
+            join_hooks: JoinHooks::default()
+        }
+    }
+}
+
+impl Broadcaster {
+    /// create a new broadcaster instance.
+    pub fn new() -> Arc<RwLock<Self>> {
+        Self::typed()
+    }
+}
+
+impl<S: Clone, R: Id, C: Id> Broadcaster<S, R, C> {
+    /// create a new broadcaster instance whose connections have a state of type `S`. `S` has to implement `Default` if you use `.handle()`, use `.handle_with_state()` otherwise.
+    ///
+    ///```rust
+    ///
+    /// use actix_wsb::Broadcaster;
+    ///
+    /// #[derive(Clone, Default)]
+    /// struct User {
+    ///     id: u64,
+    ///     admin: bool
+    /// }
+    ///
+    /// let broadcaster = Broadcaster::<User>::typed();
+    ///
+    ///```
+    pub fn typed() -> Arc<RwLock<Self>> {
+        Arc::new(RwLock::new(Self::default()))
+    }
+
+    /// does all the setup basically. You don't have to use other functions for all the grouping of rooms and connections. You can give the same room id for all instances if you don't want to seperate communication groups. But you have to give different connection id's to each session, otherwise it'll introduce bugs.
+    ///
+    /// It returns `Error::LockPoisoned` if the lock of the broadcaster is poisoned, `Error::Rejected` if a join hook rejects the connection and `Error::Duplicate` if there is a connection with that id in the room already and the duplicate policy rejects the session. The session is closed in the last two cases.
+    ///
+    ///```rust,ignore
+    ///
+    /// let id = query.id.as_ref().unwrap().to_string();
+    /// let room_id = query.room.as_ref().unwrap().to_string();
+    ///
+    /// let get_broadcaster = Broadcaster::handle(&broadcaster, &room_id, &id, session)?;
+    ///
+    ///```
+    pub fn handle<Q, K>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, conn_id: &K, session: Session) -> Result<Arc<RwLock<Self>>, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, Metadata::default(), S::default(), None)?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// same as `.handle()`, but the connection has given metadata.
+    ///
+    ///```rust,ignore
+    ///
+    /// let metadata = Metadata::new().user_id(&user.id).locale(&user.locale);
+    ///
+    /// let get_broadcaster = Broadcaster::handle_with(&broadcaster, &room_id, &id, session, metadata)?;
+    ///
+    /// // later:
+    /// get_broadcaster.write().unwrap().room(&room_id)?.broadcast_if(msg, |conn| conn.metadata.locale.as_deref() == Some("tr-TR")).await;
+    ///
+    ///```
+    pub fn handle_with<Q, K>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<Arc<RwLock<Self>>, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, metadata, S::default(), None)?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// same as `.handle()`, but the connection has given state.
+    ///
+    ///```rust,ignore
+    ///
+    /// let broadcaster = Broadcaster::<User>::typed();
+    ///
+    /// let get_broadcaster = Broadcaster::handle_with_state(&broadcaster, &room_id, &id, session, User { id: 42, admin: false })?;
+    ///
+    /// // later:
+    /// get_broadcaster.write().unwrap().room(&room_id)?.broadcast_if(msg, |conn| conn.state.admin).await;
+    ///
+    ///```
+    pub fn handle_with_state<Q, K>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<Arc<RwLock<Self>>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, Metadata::default(), state, None)?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// same as `.handle()`, but the join hooks get the handshake request of the connection in the `handshake` field of `JoinRequest`, so they can read it's headers, uri or the address of the client.
+    ///
+    ///```rust,ignore
+    ///
+    /// let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
+    ///
+    /// let get_broadcaster = Broadcaster::handle_request(&broadcaster, &req, &room_id, &id, session)?;
+    ///
+    ///```
+    pub fn handle_request<Q, K>(broadcaster: &Arc<RwLock<Self>>, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session) -> Result<Arc<RwLock<Self>>, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, Metadata::default(), S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// same as `.handle_request()`, but the connection has given metadata.
+    pub fn handle_request_with<Q, K>(broadcaster: &Arc<RwLock<Self>>, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<Arc<RwLock<Self>>, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, metadata, S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// same as `.handle_request()`, but the connection has given state.
+    pub fn handle_request_with_state<Q, K>(broadcaster: &Arc<RwLock<Self>>, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<Arc<RwLock<Self>>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        broadcaster.write()?.admit(room_id, conn_id, session, Metadata::default(), state, Some(&Handshake::from(request)))?;
+
+        Ok(Arc::clone(broadcaster))
+    }
+
+    /// this function check if a room exist and if it's exist returns it, if it's not then creates it. If you just want to check if a room exist, use .check() instead.
+    ///
+    ///```rust,ignore
+    ///
+    /// let mut broadcaster_write = broadcaster.write().unwrap();
+    ///
+    /// let room_id = "1".to_string();
+    ///
+    /// broadcaster_write.handle_room(&room_id)
+    ///
+    ///```
+    ///
+    pub fn handle_room<Q>(&mut self, id: &Q) -> &mut Room<S, R, C> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        if !self.rooms.contains_key(id) {
+            let mut room = Room::<S, R, C> {
+                id: id.to_owned(),
+                connectors: HashMap::new(),
+                events: self.events.clone(),
+                index: self.index.clone(),
+                concurrency: self.concurrency,
+                queue_capacity: self.queue_capacity,
+                slow_consumer_policy: self.slow_consumer_policy.clone(),
+                duplicate_policy: self.duplicate_policy.clone(),
+                presence: self.presence,
+                fan_out: FanOut::Direct,
+                channel: None,
+                key: room_key(),
+                graveyard: Graveyard::default()
+            };
+
+            room.set_fan_out(self.fan_out);
+
+            self.rooms.insert(id.to_owned(), room);
+            self.events.emit(Event::RoomCreated { room: id.to_owned() });
+        }
+
+        self.rooms.get_mut(id).unwrap()
+    }
+
+    /// registers a listener that will be called for every event that broadcaster and it's rooms emit, including the lifecycle events like `Event::Joined`, `Event::Closed` or `Event::RoomCreated`, check out `Event` for all of them. Listeners are called synchronously and may be called while the broadcaster is locked, so they shouldn't block or try to lock the broadcaster.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::{Broadcaster, Event};
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     broadcaster.read().unwrap().on_event(|event| {
+    ///         if let Event::Pruned { room, connection } = event {
+    ///             println!("{} is disconnected from {}", connection, room);
+    ///         }
+    ///     });
+    /// }
+    ///
+    /// ```
+    pub fn on_event<F>(&self, listener: F) where F: Fn(&Event<R, C>) + Send + Sync + 'static {
+        self.events.push(listener);
+    }
+
+    /// registers a hook that decides if a connection can join a room with the `.handle()`, `.join()` or `.move_connection()` methods, before it's added. It gets the id's of the room and the connection, the metadata and the state that the connection is added with, the count of the connections in the room and the handshake request if it's added with a `.handle_request()` method, which it keeps for the `.join()` and `.move_connection()` calls after that, and rejects the join by returning a `CloseReason`. Then the session is closed with that reason and the `.handle()` method returns `Error::Rejected`. A rejected `.join()` or `.move_connection()` returns `Error::Rejected` too, but the connection stays open in it's other rooms. Hooks are called in the order they registered, under the lock of the broadcaster, so they shouldn't block or try to lock the broadcaster. `.add_connection()` methods of `Room` don't call them.
+    ///
+    /// ```rust
+    ///
+    /// use actix_ws::{CloseCode, CloseReason};
+    /// use actix_wsb::Broadcaster;
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     broadcaster.read().unwrap().on_join(|request| {
+    ///         if request.members >= 100 {
+    ///             return Err(CloseReason { code: CloseCode::Again, description: Some("room is full".to_string()) });
+    ///         }
+    ///
+    ///         if request.room == "admins" && !request.metadata.has_role("admin") {
+    ///             return Err(CloseReason { code: CloseCode::Policy, description: Some("admins only".to_string()) });
+    ///         }
+    ///
+    ///         Ok(())
+    ///     });
+    /// }
+    ///
+    /// ```
+    pub fn on_join<F>(&self, hook: F) where F: Fn(&JoinRequest<S, R, C>) -> Result<(), CloseReason> + Send + Sync + 'static {
+        self.join_hooks.push(hook);
+    }
+
+    /// starts the heartbeat supervisor on the current actix runtime, so it has to be called inside of it. It pings every connection of every room at the interval of given config and closes and removes the connections that didn't answer `max_missed` pings in a row, emitting `Event::TimedOut` for each of them. Record the pongs with `.record_pong()` for that. The supervisor stops when the broadcaster is dropped, or you can abort it with the returned handle.
+    ///
+    /// ```rust,ignore
+    ///
+    /// let broadcaster = Broadcaster::new();
+    ///
+    /// Broadcaster::heartbeat(&broadcaster, HeartbeatConfig {
+    ///     interval: Duration::from_secs(5),
+    ///     max_missed: 2,
+    ///     ..Default::default()
+    /// });
+    ///
+    /// ```
+    pub fn heartbeat(broadcaster: &Arc<RwLock<Self>>, config: HeartbeatConfig) -> JoinHandle<()> where S: Send + Sync + 'static {
+        actix_web::rt::spawn(heartbeat::supervise(Arc::downgrade(broadcaster), config))
+    }
+
+    /// records that a pong with given payload is received from the connection with given id, in any room. Round-trip time is also recorded if it's the pong of a timestamped ping. Returns `Error::ConnectionNotFound` if there is no connection with given id.
+    ///
+    ///```rust,ignore
+    ///
+    /// Message::Pong(bytes) => {
+    ///     let _ = get_broadcaster.read().unwrap().record_pong(&id, &bytes);
+    /// },
+    ///
+    ///```
+    pub fn record_pong<K>(&self, conn_id: &K, bytes: &[u8]) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.index.rooms_of(conn_id) {
+            if let Some(connection) = self.rooms.get(&room_id).and_then(|room| room.connectors.get(conn_id)) {
+                connection.record_pong(bytes);
+
+                found = true;
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    /// changes the metadata of the connection with given id in every room that it's in. Returns `Error::ConnectionNotFound` if it's not in any room.
+    ///
+    ///```rust,ignore
+    ///
+    /// broadcaster.write().unwrap().update_metadata(&id, |metadata| metadata.locale = Some("de-DE".to_string()))?;
+    ///
+    ///```
+    pub fn update_metadata<F, K>(&mut self, conn_id: &K, mut f: F) -> Result<(), Error> where F: FnMut(&mut Metadata), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.index.rooms_of(conn_id) {
+            if let Some(room) = self.rooms.get_mut(&room_id) {
+                found |= room.update_metadata(conn_id, &mut f).is_ok();
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    /// changes the state of the connection with given id in every room that it's in. Returns `Error::ConnectionNotFound` if it's not in any room.
+    pub fn update_state<F, K>(&mut self, conn_id: &K, mut f: F) -> Result<(), Error> where F: FnMut(&mut S), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.index.rooms_of(conn_id) {
+            if let Some(room) = self.rooms.get_mut(&room_id) {
+                found |= room.update_state(conn_id, &mut f).is_ok();
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    /// sets how many connections of a room can be sent to at the same time, for every room that exist and will be created. `None` means there is no limit, which is the default. Use `.set_concurrency()` method of `Room` if you want to change it for a single room.
+    pub fn set_concurrency(&mut self, limit: Option<usize>) {
+        self.concurrency = limit;
+
+        for room in self.rooms.values_mut() {
+            room.set_concurrency(limit);
+        }
+    }
+
+    /// sets how many messages the outbound queue of a connection can hold, for the rooms that exist and will be created. It affects the connections that will be added after that, default is 64. When a queue is full, sends to that connection fail with `Error::QueueFull`.
+    pub fn set_queue_capacity(&mut self, capacity: usize) {
+        self.queue_capacity = capacity;
+
+        for room in self.rooms.values_mut() {
+            room.set_queue_capacity(capacity);
+        }
+    }
+
+    /// sets what happens when the outbound queue of a connection is full, for the rooms that exist and will be created. It affects the connections that will be added after that, default is `SlowConsumerPolicy::DropNewest`. Use `.set_slow_consumer_policy()` method of `Connection` if you want to change it for a single connection.
+    ///
+    /// ```rust
+    ///
+    /// use std::time::Duration;
+    /// use actix_wsb::{Broadcaster, SlowConsumerPolicy};
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     broadcaster.write().unwrap().set_slow_consumer_policy(SlowConsumerPolicy::Block(Duration::from_millis(50)));
+    /// }
+    ///
+    /// ```
+    pub fn set_slow_consumer_policy(&mut self, policy: SlowConsumerPolicy) {
+        self.slow_consumer_policy = policy.clone();
+
+        for room in self.rooms.values_mut() {
+            room.set_slow_consumer_policy(policy.clone());
+        }
+    }
+
+    /// sets what happens when a session is added with the id of a connection that's already in it's room, for the rooms that exist and will be created. Default is closing the existing connection and adding the new session instead, check out `DuplicatePolicy` for the others. Use `.set_duplicate_policy()` method of `Room` if you want to change it for a single room.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::{Broadcaster, DuplicatePolicy};
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     // multiple tabs of the same user share the connection id:
+    ///     broadcaster.write().unwrap().set_duplicate_policy(DuplicatePolicy::Allow);
+    /// }
+    ///
+    /// ```
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy.clone();
+
+        for room in self.rooms.values_mut() {
+            room.set_duplicate_policy(policy.clone());
+        }
+    }
+
+    /// enables or disables the presence of the rooms that exist and will be created, it's disabled by default. Use `.set_presence()` method of `Room` if you want to change it for a single room, check it out for the messages that sent.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::Broadcaster;
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     broadcaster.write().unwrap().set_presence(true);
+    /// }
+    ///
+    /// ```
+    pub fn set_presence(&mut self, enabled: bool) {
+        self.presence = enabled;
+
+        for room in self.rooms.values_mut() {
+            room.set_presence(enabled);
+        }
+    }
+
+    /// sets how a room sends a message to it's connections, for the rooms that exist and will be created. Use `.set_fan_out()` method of `Room` if you want to change it for a single room.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::{Broadcaster, FanOut};
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     // every connection pulls the messages of it's room from a channel that holds 1024 messages:
+    ///     broadcaster.write().unwrap().set_fan_out(FanOut::Channel(1024));
+    /// }
+    ///
+    /// ```
+    pub fn set_fan_out(&mut self, fan_out: FanOut) {
+        self.fan_out = fan_out;
+
+        for room in self.rooms.values_mut() {
+            room.set_fan_out(fan_out);
+        }
+    }
+
+    /// sets how the broadcaster generates the id's of the connections that added with the `.handle_generated()` methods, default is `IdGenerator::UuidV4`.
+    pub fn set_id_generator(&mut self, generator: IdGenerator) {
+        self.ids.generator = generator;
+    }
+
+    /// it scans a room with given id and it returns it if it's exist, otherwise returns `Error::RoomNotFound`. If you want to get an option instead, use ".check_room()"
+    pub fn room<Q>(&mut self, id: &Q) -> Result<&mut Room<S, R, C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.rooms.get_mut(id).ok_or_else(|| Error::RoomNotFound(id.to_string()))
+    }
+
+    /// checks a room and if it's exist, returns a mutable reference of that room.
+    pub fn check_room<Q>(&mut self, id: &Q) -> Option<&mut Room<S, R, C>> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.rooms.get_mut(id)
+    }
+
+    /// it returns room if exist with given ip. Use .handle_room() method if you want to create a room with given id.
+    pub fn check<Q>(&self, id: &Q) -> bool where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.rooms.contains_key(id)
+    }
+
+    /// iterates through every room and does something with them immutably. You cannot mutate anything inside of it, even rooms and not captured variables.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::Broadcaster;
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     broadcaster.read().unwrap().each_room_immut(|room| println!("hello, {}. guest!", room.id));
+    /// }
+    ///
+    ///
+    /// ```
+    pub fn each_room_immut<F>(&self, f: F) where F: Fn(&Room<S, R, C>) {
+        for room in self.rooms.values() {
+            f(room);
+        }
+    }
+
+    /// iterates through every room and does something with them immutably. You cannot mutate rooms itself but can mutate captured variables.
+    ///
+    /// ```rust
+    ///
+    /// use actix_wsb::Broadcaster;
+    ///
+    /// fn main () {
+    ///     let broadcaster = Broadcaster::new();
+    ///
+    ///     let mut num = 0;
+    ///
+    ///     broadcaster.read().unwrap().each_room(|room| {
+    ///         num = num + 1;
+    ///     });
+    ///
+    ///     println!("here is number: {}", num)
+    /// }
+    ///
+    ///
+    /// ```
+    pub fn each_room<F>(&self, mut f: F) where F: FnMut(&Room<S, R, C>) {
+        for room in self.rooms.values() {
+            f(room);
+        }
+    }
+
+    /// iterates through every room and does something with them mutably. You can mutate everything belong to it. But warning, for now, you cannot send messages to client from it right now and until async closures will be stable probably we're not be able to do it. Because of that, we're not able to give examples for that.
+    pub async fn each_room_mut<F>(&mut self, mut f: F) where F: FnMut(&mut Room<S, R, C>) {
+        for room in self.rooms.values_mut() {
+            f(room);
+        }
+    }
+
+    /// it removes a room with given id and returns the `Closing` that closes all the connections of it concurrently when it's awaited, so the lock of the broadcaster can be released before that. It's output is a `DeliveryReport` of the closes, or `Error::RoomNotFound` if there is no room with given id.
+    ///
+    ///
+    /// ```rust,ignore
+    /// Message::Close(reason) => {
+    ///     // warning, that closes and removes all the connections but not removes the room:
+    ///     //let closing = get_broadcaster.write().unwrap().room(&room_id)?.close(reason);
+    ///
+    ///     // if you want to remove a room with removing all the connections, use this instead:
+    ///     let closing = get_broadcaster.write().unwrap().remove_room(&room_id);
+    ///     let _ = closing.await;
+    ///
+    ///     break;
+    ///  },
+    /// ```
+    ///
+    pub fn remove_room<Q>(&mut self, id: &Q) -> Closing<Result<DeliveryReport<C>, Error>> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        match self.rooms.remove(id) {
+            Some(mut room) => {
+                let closing = room.close(None);
+                self.events.emit(Event::RoomRemoved { room: room.id.clone() });
+
+                Closing::new(async move { Ok(closing.await) })
+            },
+            None => Closing::ready(Err(Error::RoomNotFound(id.to_string())))
+        }
+    }
+
+    /// it removes all empty rooms and emits an `Event::RoomRemoved` for each of them. The connections that are closed in another room or whose session is gone are pruned first, so a room whose connections are all disconnected is removed too.
+    pub fn remove_empty_rooms(&mut self) {
+        let events = &self.events;
+
+        self.rooms.retain(|id, room| {
+            room.reap();
+
+            let keep = !room.connectors.is_empty();
+
+            if !keep {
+                events.emit(Event::RoomRemoved { room: id.clone() });
+            }
+
+            keep
+        });
+    }
+
+    /// adds the connection with given id to the room with given id too, creates the room if it's not exist. The connection has to be in another room already, it returns `Error::ConnectionNotFound` otherwise. It copies the metadata and state of the connection in the other room and shares it's session, outbound queue, heartbeat and round-trip time statistics, so the messages of all the rooms are sent in the order they're queued and the settings of the new room, like the queue capacity, don't apply to it. Continuation frames that sent to different rooms at the same time can interleave, so send a fragmented message to one room at a time. It does nothing if the connection is in that room already.
+    ///
+    ///```rust,ignore
+    ///
+    /// let get_broadcaster = Broadcaster::handle(&broadcaster, &format!("user-{}", user_id), &id, session)?;
+    ///
+    /// let mut broadcaster_write = get_broadcaster.write().unwrap();
+    ///
+    /// broadcaster_write.join(&team_id, &id)?;
+    /// broadcaster_write.join("notifications", &id)?;
+    ///
+    ///```
+    pub fn join<Q, K>(&mut self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.find(conn_id).ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+        let hooks = self.join_hooks.clone();
+        let room = self.handle_room(room_id);
+
+        room.welcome(&hooks, &connection)?;
+        room.join_copy(&connection);
+
+        Ok(())
+    }
+
+    /// removes the connection with given id from the room with given id without closing it, it stays in it's other rooms. Returns `Error::RoomNotFound` or `Error::ConnectionNotFound` if one of them is not exist.
+    pub fn leave<Q, K>(&mut self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.room(room_id)?.part(conn_id).map(|_| ()).ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))
+    }
+
+    /// returns the id's of the rooms that the connection with given id is in.
+    pub fn rooms_of<K>(&self, conn_id: &K) -> Vec<R> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.index.rooms_of(conn_id)
+    }
+
+    /// moves the connection with given id from a room to another one with it's outbound queue, creates the target room if it's not exist. No broadcast can happen in the middle of it, so the connection gets every message of the source room that sent before and every message of the target room that sent after. Emits an `Event::Left` for the source room and an `Event::Joined` for the target room. If the connection is in the target room already, it's only removed from the source room.
+    ///
+    /// It returns `Error::RoomNotFound` if the source room is not exist and `Error::ConnectionNotFound` if the connection is not in it. The messages of a `FanOut::Channel` source room that the connection didn't pull yet are skipped.
+    ///
+    ///```rust,ignore
+    ///
+    /// broadcaster.write().unwrap().move_connection(&id, "lobby", &game_id)?;
+    ///
+    ///```
+    pub fn move_connection<K, Q>(&mut self, conn_id: &K, from: &Q, to: &Q) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let connection = self.room(from)?.check_connection(conn_id).ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+        let hooks = self.join_hooks.clone();
+
+        // the hooks decide before the connection leaves the source room, so a rejected move doesn't change anything.
+        self.handle_room(to).welcome(&hooks, &connection)?;
+
+        let connection = self.room(from)?.part(conn_id).ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+
+        self.handle_room(to).adopt(connection);
+
+        Ok(())
+    }
+
+    /// closes the connection with given id and removes it from every room that it's in, use it when the client disconnects. Returns `Error::ConnectionNotFound` if it's not in any room. It closes every session of the connection with `DuplicatePolicy::Allow`, use `.close_session()` to close one of them.
+    ///
+    ///```rust,ignore
+    ///
+    /// Message::Close(reason) => {
+    ///     let closing = get_broadcaster.write().unwrap().disconnect(reason, &id);
+    ///     let _ = closing.await;
+    ///
+    ///     break;
+    /// },
+    ///
+    ///```
+    pub fn disconnect<K>(&mut self, reason: Option<CloseReason>, conn_id: &K) -> Closing<Result<(), Error>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let memberships = self.take_all(conn_id, Room::evict);
+
+        close_memberships(memberships, reason, conn_id)
+    }
+
+    /// closes given session of the connection with given id, use it instead of `.disconnect()` when a tab disconnects with `DuplicatePolicy::Allow`. The connection stays in it's rooms with it's other sessions, or it's closed and removed from every room like `.disconnect()` if that was the last one.
+    ///
+    ///```rust,ignore
+    ///
+    /// Message::Close(reason) => {
+    ///     let closing = get_broadcaster.write().unwrap().close_session(reason, &id, session);
+    ///     let _ = closing.await;
+    ///
+    ///     break;
+    /// },
+    ///
+    ///```
+    pub fn close_session<K>(&mut self, reason: Option<CloseReason>, conn_id: &K, session: Session) -> Closing<Result<(), Error>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.find(conn_id) {
+            Some(connection) if connection.outbound.release() => Closing::new(close_released(session, reason)),
+            Some(_) => self.disconnect(reason, conn_id),
+            None => Closing::ready(Err(Error::ConnectionNotFound(conn_id.to_string())))
+        }
+    }
+
+    /// it removes a connection from every room that it's in and returns the session struct of it, returns `Error::ConnectionNotFound` if there is no connection with given id. Since that method doesn't close the actual "Session" implementation, you have to close that connection manually - check out the example and readme.
+    /// This is the old way of removing connections. It'll not be removed but we don't recommend to use it unless you don't used it yet, use `.disconnect()` instead.
+    pub fn remove_connection<K>(&mut self, id: &K) -> Result<Session, Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.take_all(id, Room::part).into_iter().next() {
+            Some(connection) => Ok(connection.session),
+            None => Err(Error::ConnectionNotFound(id.to_string()))
+        }
+    }
+
+    /// adds the connection to the room with given id through the join hooks, creates the room if it's not exist.
+    pub(crate) fn admit<Q, K>(&mut self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata, state: S, handshake: Option<&Handshake>) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        let hooks = self.join_hooks.clone();
+
+        self.handle_room(room_id).admit(&hooks, conn_id, session, metadata, state, handshake)
+    }
+
+    /// returns a copy of the connection with given id from one of it's rooms.
+    fn find<K>(&self, conn_id: &K) -> Option<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.index.rooms_of(conn_id).iter().find_map(|room_id| self.rooms.get(room_id)?.connectors.get(conn_id).cloned())
+    }
+
+    /// removes the connection with given id from every room that it's in with given method of the room, `Room::part` or `Room::evict`, and returns them.
+    pub(crate) fn take_all<K>(&mut self, conn_id: &K, remove: Removal<S, R, C, K>) -> Vec<Connection<S, C>> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.index.rooms_of(conn_id).iter().filter_map(|room_id| remove(self.rooms.get_mut(room_id)?, conn_id)).collect()
+    }
+}
+
+impl<S: Clone, R: Id> Broadcaster<S, R, String> {
+    /// same as `.handle()`, but the broadcaster generates a unique id for the connection instead of taking it from the client and returns it. Use `.set_id_generator()` to choose how the id's are generated.
+    ///
+    ///```rust,ignore
+    ///
+    /// let id = Broadcaster::handle_generated(&broadcaster, &room_id, session.clone())?;
+    ///
+    /// // send the id to the client if it needs to know it:
+    /// session.text(format!("{{\"id\":\"{}\"}}", id)).await?;
+    ///
+    ///```
+    pub fn handle_generated<Q>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, session: Session) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        Self::handle_generated_with_state(broadcaster, room_id, session, S::default())
+    }
+
+    /// same as `.handle_generated()`, but the connection has given metadata.
+    pub fn handle_generated_with<Q>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, session: Session, metadata: Metadata) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let mut broadcaster_write = broadcaster.write()?;
+        let id = broadcaster_write.ids.next();
+
+        broadcaster_write.admit(room_id, &id, session, metadata, S::default(), None)?;
+
+        Ok(id)
+    }
+
+    /// same as `.handle_generated()`, but the connection has given state.
+    pub fn handle_generated_with_state<Q>(broadcaster: &Arc<RwLock<Self>>, room_id: &Q, session: Session, state: S) -> Result<String, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let mut broadcaster_write = broadcaster.write()?;
+        let id = broadcaster_write.ids.next();
+
+        broadcaster_write.admit(room_id, &id, session, Metadata::default(), state, None)?;
+
+        Ok(id)
+    }
+}
+
+/// sends the message to given connections concurrently, at most `limit` of them at the same time, and records the result of each of them to the report. The connections whose session is closed are listed as pruned, it's caller's job to remove them from their room.
+pub(crate) async fn fan_out<S, C: Id>(connections: &mut [Connection<S, C>], message: &Message, limit: Option<usize>, report: &mut DeliveryReport<C>) {
+    let sends = connections.iter_mut().map(|connection| async move {
+        let result = connection.enqueue(clone_message(message)).await;
+
+        (connection.id.clone(), result)
+    });
+
+    for (id, result) in concurrently(sends, limit).await {
+        match result {
+            Ok(()) => report.delivered.push(id),
+            Err(error) => {
+                if matches!(error, Error::Closed | Error::Disconnected) {
+                    report.pruned.push(id.clone());
+                }
+
+                report.failed.push((id, error))
+            }
+        }
+    }
+}
+
+/// sends close frame to the outbound queues of given connections concurrently, at most `limit` of them at the same time, and records the result of each of them to the report.
+pub(crate) async fn close_connections<C: Id>(connections: &[(C, Arc<Outbound>)], reason: Option<CloseReason>, limit: Option<usize>, report: &mut DeliveryReport<C>) {
+    let closes = connections.iter().map(|(id, outbound)| {
+        let reason = reason.clone();
+
+        async move { (id.clone(), outbound.close(reason).await) }
+    });
+
+    for (id, result) in concurrently(closes, limit).await {
+        match result {
+            Ok(()) => report.delivered.push(id),
+            Err(error) => report.failed.push((id, error))
+        }
+    }
+}
+
+/// closes the session of a connection that removed from all of it's rooms. The memberships that joined with `.join()` share the outbound queue of the first one, but a connection that's added to many rooms with `.handle()` has a queue in each of them, so the other queues send their messages first and then the first one sends the close frame after it's own. Returns `Error::ConnectionNotFound` if there is no membership.
+pub(crate) fn close_memberships<S, C: Id, K>(memberships: Vec<Connection<S, C>>, reason: Option<CloseReason>, conn_id: &K) -> Closing<Result<(), Error>> where K: Display + ?Sized {
+    let mut memberships = memberships.into_iter();
+
+    let Some(first) = memberships.next() else {
+        return Closing::ready(Err(Error::ConnectionNotFound(conn_id.to_string())));
+    };
+
+    let others: Vec<Arc<Outbound>> = memberships.filter(|connection| !connection.same(&first)).map(|connection| connection.outbound).collect();
+    let first = first.outbound;
+
+    Closing::new(async move {
+        concurrently(others.iter().map(|outbound| outbound.finish()), None).await;
+
+        first.close(reason).await
+    })
+}
+
+/// sends the close frame to a session that's released from it's connection, it gives up and drops the session after 5 seconds like the outbound queues.
+pub(crate) async fn close_released(session: Session, reason: Option<CloseReason>) -> Result<(), Error> {
+    match timeout(CLOSE_TIMEOUT, session.close(reason)).await {
+        Ok(result) => result.map_err(|_| Error::Closed),
+        Err(_) => Err(Error::Timeout)
+    }
+}
+
+/// polls the futures concurrently in a single task, at most `limit` of them at the same time, and returns their outputs in the order they complete.
+async fn concurrently<I, F>(futures: I, limit: Option<usize>) -> Vec<F::Output> where I: IntoIterator<Item = F>, F: Future {
+    let futures: Vec<F> = futures.into_iter().collect();
+    let limit = limit.unwrap_or(futures.len()).max(1);
+
+    stream::iter(futures).buffer_unordered(limit).collect().await
+}
+
+/// sends the message to the session, it's used by the writer task of connections.
+pub(crate) async fn write(session: &mut Session, message: Message) -> Result<(), actix_ws::Closed> {
+    match message {
+        Message::Text(text) => session.text(text).await,
+        Message::Binary(bytes) => session.binary(bytes).await,
+        Message::Ping(bytes) => session.ping(&bytes).await,
+        Message::Pong(bytes) => session.pong(&bytes).await,
+        Message::Continuation(item) => session.continuation(item).await,
+        Message::Close(reason) => session.clone().close(reason).await,
+        Message::Nop => Ok(())
+    }
+}
+
+/// `Message` doesn't implement `Clone`, so we have to copy it by hand for each connection.
+fn clone_message(message: &Message) -> Message {
+    match message {
+        Message::Text(text) => Message::Text(text.clone()),
+        Message::Binary(bytes) => Message::Binary(bytes.clone()),
+        Message::Ping(bytes) => Message::Ping(bytes.clone()),
+        Message::Pong(bytes) => Message::Pong(bytes.clone()),
+        Message::Continuation(item) => Message::Continuation(clone_item(item)),
+        Message::Close(reason) => Message::Close(reason.clone()),
+        Message::Nop => Message::Nop
+    }
+}
+
+/// `Item` doesn't implement `Clone`, so we have to copy it by hand for each connection.
+fn clone_item(item: &Item) -> Item {
+    match item {
+        Item::FirstText(text) => Item::FirstText(text.clone()),
+        Item::FirstBinary(binary) => Item::FirstBinary(binary.clone()),
+        Item::Continue(cont_msg) => Item::Continue(cont_msg.clone()),
+        Item::Last(last_msg) => Item::Last(last_msg.clone())
+    }
+}
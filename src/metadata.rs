@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
+/// attributes of a connection, like the user that it belongs to, it's roles and locale, and any other key/value pair. They're readable in the conditions of `_if` and `_if_not` methods, so you don't have to keep them anywhere else.
+///
+/// ```rust
+///
+/// use actix_wsb::Metadata;
+///
+/// let metadata = Metadata::new().user_id("42")
+///                               .role("admin")
+///                               .locale("tr-TR")
+///                               .tag("team", "backend");
+///
+/// assert!(metadata.has_role("admin"));
+/// assert_eq!(metadata.get("team"), Some("backend"));
+///
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// id of the user that the connection belongs to.
+    pub user_id: Option<String>,
+    /// roles of the connection.
+    pub roles: HashSet<String>,
+    /// locale of the connection, like "en-US".
+    pub locale: Option<String>,
+    /// any other attribute of the connection.
+    pub tags: HashMap<String, String>
+}
+
+impl Metadata {
+    /// creates an empty metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the user id.
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    /// adds a role.
+    pub fn role(mut self, role: impl Into<String>) -> Self {
+        self.roles.insert(role.into());
+        self
+    }
+
+    /// sets the locale.
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// sets the value of a tag.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// checks if the connection has given role.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+
+    /// returns the value of the tag with given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::{Display, Write};
+
+use crate::Metadata;
+
+// the json messages that a room with presence sends to it's connections. The crate doesn't depend on serde, so they're written by hand.
+
+/// the presence list that a connection gets when it joins: `{"type":"presence_state","members":[{"id":"a","metadata":{..}}]}`
+pub(crate) fn state<'a, C: Display + 'a>(members: impl Iterator<Item = (&'a C, &'a Metadata)>) -> String {
+    let mut out = String::from("{\"type\":\"presence_state\",\"members\":[");
+
+    for (position, (id, metadata)) in members.enumerate() {
+        if position > 0 {
+            out.push(',');
+        }
+
+        out.push_str("{\"id\":");
+        id_string(&mut out, id);
+        out.push_str(",\"metadata\":");
+        metadata_object(&mut out, metadata);
+        out.push('}');
+    }
+
+    out.push_str("]}");
+    out
+}
+
+/// `{"type":"presence_join","id":"a","metadata":{..}}`
+pub(crate) fn joined<C: Display>(id: &C, metadata: &Metadata) -> String {
+    delta("presence_join", id, Some(metadata))
+}
+
+/// `{"type":"presence_update","id":"a","metadata":{..}}`
+pub(crate) fn updated<C: Display>(id: &C, metadata: &Metadata) -> String {
+    delta("presence_update", id, Some(metadata))
+}
+
+/// `{"type":"presence_leave","id":"a"}`
+pub(crate) fn left<C: Display>(id: &C) -> String {
+    delta("presence_leave", id, None)
+}
+
+fn delta<C: Display>(kind: &str, id: &C, metadata: Option<&Metadata>) -> String {
+    let mut out = format!("{{\"type\":\"{}\",\"id\":", kind);
+
+    id_string(&mut out, id);
+
+    if let Some(metadata) = metadata {
+        out.push_str(",\"metadata\":");
+        metadata_object(&mut out, metadata);
+    }
+
+    out.push('}');
+    out
+}
+
+/// `{"user_id":"42","roles":["admin"],"locale":null,"tags":{"team":"backend"}}`
+fn metadata_object(out: &mut String, metadata: &Metadata) {
+    out.push_str("{\"user_id\":");
+    optional_string(out, metadata.user_id.as_deref());
+    out.push_str(",\"roles\":[");
+
+    for (position, role) in metadata.roles.iter().enumerate() {
+        if position > 0 {
+            out.push(',');
+        }
+
+        string(out, role);
+    }
+
+    out.push_str("],\"locale\":");
+    optional_string(out, metadata.locale.as_deref());
+    out.push_str(",\"tags\":{");
+
+    for (position, (key, value)) in metadata.tags.iter().enumerate() {
+        if position > 0 {
+            out.push(',');
+        }
+
+        string(out, key);
+        out.push(':');
+        string(out, value);
+    }
+
+    out.push_str("}}");
+}
+
+/// id's are written as strings whatever their type is, with their `Display` implementation.
+fn id_string<C: Display>(out: &mut String, id: &C) {
+    string(out, &id.to_string());
+}
+
+fn optional_string(out: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => string(out, value),
+        None => out.push_str("null")
+    }
+}
+
+/// writes the value as a json string, escaping the quotes, backslashes and control characters.
+fn string(out: &mut String, value: &str) {
+    out.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            character if character.is_control() => { let _ = write!(out, "\\u{:04x}", character as u32); },
+            character => out.push(character)
+        }
+    }
+
+    out.push('"');
+}
@@ -0,0 +1,638 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::{timeout, Instant};
+use actix_ws::{CloseReason, Item, Message, Session};
+use futures_util::future::{select, select_all, Either};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{oneshot, Notify};
+
+use crate::{clone_message, write, Error};
+
+/// default capacity of the outbound queue of a connection.
+pub(crate) const DEFAULT_CAPACITY: usize = 64;
+
+/// how long closing a connection waits for the writer task to send the queued messages and the close frame. A client that stops reading blocks the writes of it's session, so the writer task is aborted after that.
+pub(crate) const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how many presence messages the outbound queue of a connection can hold beyond it's capacity, so a burst of joins and leaves doesn't hit the slow consumer policy.
+pub(crate) const PRESENCE_SLOTS: usize = 8;
+
+/// the channel that the result of the close frame is sent to.
+type CloseReply = oneshot::Sender<Result<(), Error>>;
+
+/// a function that's called when the writer task stops, it tells a room that the connection is gone.
+pub(crate) type Watcher = Box<dyn FnOnce() + Send>;
+
+/// returns a unique key for a room, which tells it's subscription apart from the ones of the other rooms in the queue of a connection that's in many of them.
+pub(crate) fn room_key() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// the subscription of a connection to the channel of a room whose fan out is `FanOut::Channel`. The writer task pulls the messages of the room from it when it's own queue is empty.
+pub(crate) struct Subscription {
+    pub(crate) receiver: broadcast::Receiver<Arc<Message>>,
+    /// called with the count of the messages that skipped because the connection fell behind the channel.
+    pub(crate) on_lag: Box<dyn Fn(u64) + Send>
+}
+
+/// what happens when a message is sent to a connection whose outbound queue is full, because the client can't keep up.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SlowConsumerPolicy {
+    /// the new message is dropped and the send fails with `Error::QueueFull`. This is the default.
+    #[default]
+    DropNewest,
+    /// the oldest message in the queue is dropped to make room for the new one, the send succeeds. A fragmented message is dropped as a whole, and the presence messages, the control frames and the rest of a fragmented message that's partly sent are never dropped. If there is nothing else to drop, the new message is dropped like `DropNewest`.
+    DropOldest,
+    /// the queued messages are discarded and the connection is closed with given reason, the send fails with `Error::Disconnected`.
+    Disconnect(Option<CloseReason>),
+    /// the send waits until the queue has room for given duration at most, then fails with `Error::Timeout`.
+    Block(Duration)
+}
+
+/// the producer side of the outbound queue of a connection. It's shared between the clones of a connection, which are also it's memberships in the other rooms, when the last of them is dropped the writer task sends the remaining messages and stops.
+pub(crate) struct Outbound {
+    queue: Arc<Queue>,
+    writer: JoinHandle<()>
+}
+
+/// the state that shared between the producers and the writer task.
+struct Queue {
+    state: Mutex<State>,
+    readable: Notify,
+    writable: Notify
+}
+
+struct State {
+    frames: VecDeque<Queued>,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    /// count of the messages that dropped because the queue was full.
+    dropped: u64,
+    /// no more messages are accepted, the writer stops when the frames are sent.
+    finished: bool,
+    /// the close frame that will be sent after the remaining frames, with the channel that the result of it will be sent.
+    closing: Option<(Option<CloseReason>, Option<CloseReply>)>,
+    /// the subscriptions that will replace the ones of the writer task with the same room key, `None` means it'll unsubscribe from that room.
+    resubscribe: Vec<(u64, Option<Subscription>)>,
+    /// count of the messages of the room channel that skipped because the connection fell behind.
+    lagged: u64,
+    /// the sessions that will be added to the ones that the writer task sends to, with the message that's sent to each of them before the others.
+    attached: Vec<(Session, Option<Message>)>,
+    /// count of the sessions that aren't released or dropped by the writer task yet.
+    tabs: usize,
+    /// the channel that's notified when the writer task stops after sending the remaining frames.
+    stopped: Option<oneshot::Sender<()>>,
+    /// the rooms that the connection is in by their key, they're told when the writer task stops.
+    watchers: Vec<(u64, Watcher)>,
+    /// the writer task is stopped.
+    done: bool
+}
+
+/// a message in the queue.
+struct Queued {
+    message: Message,
+    /// it's a presence message, which `SlowConsumerPolicy::DropOldest` never drops.
+    presence: bool
+}
+
+enum Next {
+    Frame(Message),
+    Close(Option<CloseReason>, Option<CloseReply>),
+    Wait,
+    Stop
+}
+
+impl Outbound {
+    /// creates the queue and spawns it's writer task on the current actix runtime.
+    pub(crate) fn spawn(session: Session, capacity: usize, policy: SlowConsumerPolicy) -> Self {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(State {
+                frames: VecDeque::new(),
+                capacity: capacity.max(1),
+                policy,
+                dropped: 0,
+                finished: false,
+                closing: None,
+                resubscribe: vec![],
+                lagged: 0,
+                attached: vec![],
+                tabs: 1,
+                stopped: None,
+                watchers: vec![],
+                done: false
+            }),
+            readable: Notify::new(),
+            writable: Notify::new()
+        });
+
+        let writer = actix_web::rt::spawn(Arc::clone(&queue).drain(session));
+
+        Self { queue, writer }
+    }
+
+    /// puts the message at the end of the queue. Returns `Error::Closed` if the session is closed, if the queue is full it applies the slow consumer policy of the queue. It only waits with `SlowConsumerPolicy::Block`.
+    pub(crate) async fn push(&self, message: Message) -> Result<(), Error> {
+        let mut deadline = None;
+
+        loop {
+            // registers before checking the queue, so a message that sent out meanwhile can't be missed.
+            let mut writable = std::pin::pin!(self.queue.writable.notified());
+            writable.as_mut().enable();
+
+            let wait = {
+                let mut state = self.queue.lock();
+
+                if state.finished {
+                    return Err(Error::Closed);
+                }
+
+                if state.frames.len() < state.capacity {
+                    state.frames.push_back(Queued { message, presence: false });
+                    drop(state);
+
+                    self.queue.readable.notify_one();
+
+                    return Ok(());
+                }
+
+                match state.policy.clone() {
+                    SlowConsumerPolicy::DropNewest => {
+                        state.dropped += 1;
+
+                        return Err(Error::QueueFull);
+                    },
+                    SlowConsumerPolicy::DropOldest => {
+                        state.dropped += 1;
+
+                        // falls back to dropping the new message if there is nothing that can be dropped.
+                        if !state.drop_oldest() {
+                            return Err(Error::QueueFull);
+                        }
+
+                        state.frames.push_back(Queued { message, presence: false });
+
+                        return Ok(());
+                    },
+                    SlowConsumerPolicy::Disconnect(reason) => {
+                        state.dropped += 1;
+                        drop(state);
+
+                        self.abort(reason);
+
+                        return Err(Error::Disconnected);
+                    },
+                    SlowConsumerPolicy::Block(duration) => duration
+                }
+            };
+
+            let deadline = *deadline.get_or_insert_with(|| Instant::now() + wait);
+
+            if timeout(deadline.saturating_duration_since(Instant::now()), writable).await.is_err() {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// puts the message at the end of the queue if it has room, without applying the slow consumer policy. It's used for the control frames of the broadcaster itself.
+    pub(crate) fn try_push(&self, message: Message) -> Result<(), Error> {
+        let mut state = self.queue.lock();
+
+        if state.finished {
+            return Err(Error::Closed);
+        }
+
+        if state.frames.len() >= state.capacity {
+            return Err(Error::QueueFull);
+        }
+
+        state.frames.push_back(Queued { message, presence: false });
+        drop(state);
+
+        self.queue.readable.notify_one();
+
+        Ok(())
+    }
+
+    /// puts the presence message at the end of the queue, it can use `PRESENCE_SLOTS` more slots than the capacity. When they're full too, the slow consumer policy applies without waiting, so `SlowConsumerPolicy::Block` drops the message like `SlowConsumerPolicy::DropNewest`.
+    pub(crate) fn push_presence(&self, message: Message) -> Result<(), Error> {
+        let mut state = self.queue.lock();
+
+        if state.finished {
+            return Err(Error::Closed);
+        }
+
+        if state.frames.len() < state.capacity + PRESENCE_SLOTS {
+            state.frames.push_back(Queued { message, presence: true });
+            drop(state);
+
+            self.queue.readable.notify_one();
+
+            return Ok(());
+        }
+
+        state.dropped += 1;
+
+        match state.policy.clone() {
+            SlowConsumerPolicy::DropNewest | SlowConsumerPolicy::Block(_) => Err(Error::QueueFull),
+            SlowConsumerPolicy::DropOldest if state.drop_oldest() => {
+                state.frames.push_back(Queued { message, presence: true });
+
+                Ok(())
+            },
+            SlowConsumerPolicy::DropOldest => Err(Error::QueueFull),
+            SlowConsumerPolicy::Disconnect(reason) => {
+                drop(state);
+
+                self.abort(reason);
+
+                Err(Error::Disconnected)
+            }
+        }
+    }
+
+    /// discards the queued messages and sends the close frame without waiting for it. Returns false if the queue is already finished.
+    pub(crate) fn abort(&self, reason: Option<CloseReason>) -> bool {
+        let mut state = self.queue.lock();
+
+        if state.finished {
+            return false;
+        }
+
+        state.dropped += state.frames.len() as u64;
+        state.frames.clear();
+        state.finished = true;
+        state.closing = Some((reason, None));
+        drop(state);
+
+        self.queue.readable.notify_one();
+        self.queue.writable.notify_waiters();
+
+        true
+    }
+
+    /// sends the close frame after the queued messages and waits for the result of it. If the writer task can't send them in `CLOSE_TIMEOUT`, it's aborted, so it's sessions are dropped, and `Error::Timeout` is returned.
+    pub(crate) async fn close(&self, reason: Option<CloseReason>) -> Result<(), Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        {
+            let mut state = self.queue.lock();
+
+            if state.finished {
+                return Err(Error::Closed);
+            }
+
+            state.finished = true;
+            state.closing = Some((reason, Some(sender)));
+        }
+
+        self.queue.readable.notify_one();
+
+        match timeout(CLOSE_TIMEOUT, receiver).await {
+            Ok(result) => result.unwrap_or(Err(Error::Closed)),
+            Err(_) => {
+                self.kill();
+
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// stops accepting messages and waits until the writer task sends the queued ones, without closing the session. If it can't send them in `CLOSE_TIMEOUT`, it's aborted like in `.close()`.
+    pub(crate) async fn finish(&self) {
+        let (sender, receiver) = oneshot::channel();
+
+        {
+            let mut state = self.queue.lock();
+
+            if state.finished {
+                return;
+            }
+
+            state.finished = true;
+            state.stopped = Some(sender);
+        }
+
+        self.queue.readable.notify_one();
+
+        if timeout(CLOSE_TIMEOUT, receiver).await.is_err() {
+            self.kill();
+        }
+    }
+
+    /// aborts the writer task that's stuck on a client that doesn't read, which drops it's sessions, and does what it does when it stops.
+    fn kill(&self) {
+        self.writer.abort();
+        self.queue.shutdown();
+        self.queue.stop();
+    }
+
+    /// the count of the messages that waiting to be sent.
+    pub(crate) fn len(&self) -> usize {
+        self.queue.lock().frames.len()
+    }
+
+    /// the count of the messages that dropped because the queue was full.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.queue.lock().dropped
+    }
+
+    /// makes the writer task pull the messages of the room with given key from given subscription too, it replaces the current one of that room if there is.
+    pub(crate) fn subscribe(&self, room: u64, subscription: Subscription) {
+        self.queue.lock().resubscribe.push((room, Some(subscription)));
+        self.queue.readable.notify_one();
+    }
+
+    /// stops pulling the messages of the channel of the room with given key.
+    pub(crate) fn unsubscribe(&self, room: u64) {
+        self.queue.lock().resubscribe.push((room, None));
+        self.queue.readable.notify_one();
+    }
+
+    /// makes the writer task send the messages to given session too, after sending the greeting to it if there is one. Returns the session back if the queue is finished.
+    pub(crate) fn attach(&self, session: Session, greeting: Option<Message>) -> Result<(), Session> {
+        let mut state = self.queue.lock();
+
+        if state.finished {
+            return Err(session);
+        }
+
+        state.attached.push((session, greeting));
+        state.tabs += 1;
+        drop(state);
+
+        self.queue.readable.notify_one();
+
+        Ok(())
+    }
+
+    /// counts a session of the connection as closed by the caller if it has other sessions and returns true, otherwise returns false and the caller should close the whole connection.
+    pub(crate) fn release(&self) -> bool {
+        let mut state = self.queue.lock();
+
+        if state.finished || state.tabs <= 1 {
+            return false;
+        }
+
+        state.tabs -= 1;
+
+        true
+    }
+
+    /// calls given function once when the writer task stops, because the connection is closed or all of it's sessions are gone, unless it's unwatched before. It replaces the watcher of the room with given key if there is, and it's called right away if the writer task is stopped already.
+    pub(crate) fn watch(&self, room: u64, watcher: Watcher) {
+        let mut state = self.queue.lock();
+
+        if state.done {
+            drop(state);
+
+            return watcher();
+        }
+
+        state.watchers.retain(|(current, _)| *current != room);
+        state.watchers.push((room, watcher));
+    }
+
+    /// removes the watcher of the room with given key.
+    pub(crate) fn unwatch(&self, room: u64) {
+        self.queue.lock().watchers.retain(|(current, _)| *current != room);
+    }
+
+    /// returns true if the queue doesn't accept messages anymore, because the connection is closed or closing.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.queue.lock().finished
+    }
+
+    /// the count of the messages of the room channel that skipped because the connection fell behind.
+    pub(crate) fn lagged(&self) -> u64 {
+        self.queue.lock().lagged
+    }
+
+    pub(crate) fn set_policy(&self, policy: SlowConsumerPolicy) {
+        self.queue.lock().policy = policy;
+
+        // the ones that blocked should check the new policy.
+        self.queue.writable.notify_waiters();
+    }
+}
+
+impl Drop for Outbound {
+    fn drop(&mut self) {
+        self.queue.lock().finished = true;
+        self.queue.readable.notify_one();
+        self.queue.writable.notify_waiters();
+    }
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// the writer task. Sends the frames in the order they're pushed to every session of the connection, stops when all of them are closed or the queue is finished. If it's subscribed to room channels, it pulls the messages of the rooms when the queue is empty.
+    async fn drain(self: Arc<Self>, session: Session) {
+        let mut sessions = vec![session];
+        let mut subscriptions: Vec<(u64, Subscription)> = vec![];
+
+        loop {
+            let (next, attached) = {
+                let mut state = self.lock();
+
+                for (room, replacement) in state.resubscribe.drain(..) {
+                    subscriptions.retain(|(current, _)| *current != room);
+
+                    if let Some(subscription) = replacement {
+                        subscriptions.push((room, subscription));
+                    }
+                }
+
+                let attached = std::mem::take(&mut state.attached);
+                // the sessions that the writer task dropped because they're closed don't count anymore.
+                state.tabs = state.tabs.min(sessions.len() + attached.len());
+
+                let next = if let Some(queued) = state.frames.pop_front() {
+                    self.writable.notify_one();
+
+                    Next::Frame(queued.message)
+                } else if let Some((reason, sender)) = state.closing.take() {
+                    Next::Close(reason, sender)
+                } else if state.finished {
+                    if let Some(stopped) = state.stopped.take() {
+                        let _ = stopped.send(());
+                    }
+
+                    Next::Stop
+                } else {
+                    Next::Wait
+                };
+
+                (next, attached)
+            };
+
+            for (mut session, greeting) in attached {
+                if let Some(greeting) = greeting {
+                    if write(&mut session, greeting).await.is_err() {
+                        continue;
+                    }
+                }
+
+                sessions.push(session);
+            }
+
+            match next {
+                Next::Frame(frame) => {
+                    if !write_all(&mut sessions, frame).await {
+                        self.shutdown();
+
+                        break;
+                    }
+                },
+                Next::Close(reason, sender) => {
+                    let mut result = Err(Error::Closed);
+
+                    for session in sessions.drain(..) {
+                        if session.close(reason.clone()).await.is_ok() {
+                            result = Ok(());
+                        }
+                    }
+
+                    if let Some(sender) = sender {
+                        let _ = sender.send(result);
+                    }
+
+                    self.shutdown();
+
+                    break;
+                },
+                Next::Wait => {
+                    if subscriptions.is_empty() {
+                        self.readable.notified().await;
+
+                        continue;
+                    }
+
+                    let pulls = subscriptions.iter_mut().map(|(_, subscription)| Box::pin(subscription.receiver.recv()));
+
+                    let (received, position) = match select(std::pin::pin!(self.readable.notified()), select_all(pulls)).await {
+                        Either::Left(_) => continue,
+                        Either::Right(((received, position, _), _)) => (received, position)
+                    };
+
+                    match received {
+                        Ok(frame) => {
+                            if !write_all(&mut sessions, clone_message(&frame)).await {
+                                self.shutdown();
+
+                                break;
+                            }
+                        },
+                        Err(RecvError::Lagged(skipped)) => {
+                            self.lock().lagged += skipped;
+
+                            (subscriptions[position].1.on_lag)(skipped);
+                        },
+                        Err(RecvError::Closed) => {
+                            subscriptions.remove(position);
+                        }
+                    }
+                },
+                Next::Stop => break
+            }
+        }
+
+        self.stop();
+    }
+
+    /// marks the writer task as stopped and tells the rooms of the connection, once.
+    fn stop(&self) {
+        let watchers = {
+            let mut state = self.lock();
+
+            if state.done {
+                return;
+            }
+
+            state.done = true;
+
+            std::mem::take(&mut state.watchers)
+        };
+
+        for (_, watcher) in watchers {
+            watcher();
+        }
+    }
+
+    /// marks the queue as finished after the session is closed, so the producers get `Error::Closed` from now on.
+    fn shutdown(&self) {
+        let mut state = self.lock();
+
+        state.finished = true;
+        state.frames.clear();
+
+        if let Some((_, Some(sender))) = state.closing.take() {
+            let _ = sender.send(Err(Error::Closed));
+        }
+
+        drop(state);
+
+        self.writable.notify_waiters();
+    }
+}
+
+impl State {
+    /// removes the oldest message that can be dropped without breaking what the client receives: a text or binary message that isn't a presence message, or a fragmented message as a whole if it's last frame is queued. The control frames and the rest of a fragmented message whose first frame is sent already are kept. Returns false if there is nothing to drop.
+    fn drop_oldest(&mut self) -> bool {
+        // the start of the fragmented message that the frames belong to, `None` if it's first frame isn't queued.
+        let mut fragment: Option<Option<usize>> = None;
+        let mut droppable = None;
+
+        for (index, queued) in self.frames.iter().enumerate() {
+            match (&queued.message, fragment) {
+                (Message::Continuation(Item::FirstText(_) | Item::FirstBinary(_)), _) => fragment = Some(Some(index)),
+                (Message::Continuation(Item::Last(_)), Some(Some(start))) => {
+                    droppable = Some(start..=index);
+                    break;
+                },
+                (Message::Continuation(Item::Last(_)), _) => fragment = None,
+                (Message::Continuation(_), None) => fragment = Some(None),
+                (Message::Text(_) | Message::Binary(_), None) if !queued.presence => {
+                    droppable = Some(index..=index);
+                    break;
+                },
+                _ => {}
+            }
+        }
+
+        match droppable {
+            Some(range) => {
+                self.frames.drain(range);
+
+                true
+            },
+            None => false
+        }
+    }
+}
+
+/// writes the frame to every session and drops the ones that are closed. Returns false if there is no session left.
+async fn write_all(sessions: &mut Vec<Session>, frame: Message) -> bool {
+    if let [session] = sessions.as_mut_slice() {
+        return write(session, frame).await.is_ok();
+    }
+
+    let mut open = Vec::with_capacity(sessions.len());
+
+    for mut session in sessions.drain(..) {
+        if write(&mut session, clone_message(&frame)).await.is_ok() {
+            open.push(session);
+        }
+    }
+
+    *sessions = open;
+
+    !sessions.is_empty()
+}
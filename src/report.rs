@@ -0,0 +1,54 @@
+use crate::{Error, Id};
+
+/// the outcome of a room-wide send. It tells which connections the message is queued for, which of them failed and why, and which of them skipped by the condition of `_if` and `_if_not` methods.
+///
+/// A message is written to the session by the writer task of the connection after the send returns, so `delivered` doesn't mean that the client received it. If the write fails, the session is closed and the connection shows up in `pruned` of the next send to the room.
+///
+/// Close methods of `Room` return it too, in that case `delivered` lists the connections that their close frame is written to the session.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryReport<C = String> {
+    /// id's of the connections that the message is queued for. Their writer tasks write it to the sessions in the background.
+    pub delivered: Vec<C>,
+    /// id's of the connections that the message couldn't be sent to, with the reason.
+    pub failed: Vec<(C, Error)>,
+    /// id's of the connections that didn't satisfy the condition, so the message isn't sent to them.
+    pub skipped: Vec<C>,
+    /// id's of the connections that removed from the room because their session was closed or they're disconnected by `SlowConsumerPolicy::Disconnect`. The ones that the message is sent to are also listed in `failed`.
+    pub pruned: Vec<C>,
+    /// count of the connections that the message is published to, when the fan out of the room is `FanOut::Channel`. `delivered` is empty in that case, because the connections pull the message from the channel later.
+    pub subscribers: usize,
+}
+
+impl<C> Default for DeliveryReport<C> {
+    fn default() -> Self {
+        Self {
+            delivered: vec![],
+            failed: vec![],
+            skipped: vec![],
+            pruned: vec![],
+            subscribers: 0
+        }
+    }
+}
+
+impl<C: Id> DeliveryReport<C> {
+    /// returns true if the message is queued for every connection that it's sent to.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// returns the id's of the connections that the message couldn't be sent to.
+    pub fn failed_ids(&self) -> Vec<C> {
+        self.failed.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    /// converts the report to a result, so it can be used with `?` operator. Returns `Error::PartialDelivery` if the message couldn't be sent to some of the connections.
+    pub fn into_result(self) -> Result<Self, Error> {
+        if self.is_complete() {
+            Ok(self)
+        } else {
+            Err(Error::PartialDelivery(self.failed.iter().map(|(id, _)| id.to_string()).collect()))
+        }
+    }
+}
@@ -0,0 +1,675 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use actix_web::rt::task::JoinHandle;
+use actix_web::HttpRequest;
+use actix_ws::{CloseReason, Message, Session};
+
+use crate::admission::JoinHooks;
+use crate::event::Listeners;
+use crate::generator::Ids;
+use crate::handle::Snapshot;
+use crate::heartbeat::{self, collect_targets, Expired, Supervised, Targets};
+use crate::index::Index;
+use crate::queue::{room_key, DEFAULT_CAPACITY};
+use crate::surface::send_methods;
+use crate::{close_memberships, close_released, fan_out, Connection, DeliveryReport, DuplicatePolicy, Error, Event, FanOut, Graveyard, Handshake, HeartbeatConfig, Id, IdGenerator, JoinRequest, Metadata, Removal, Room, SlowConsumerPolicy};
+
+/// default count of the shards of a `ShardedBroadcaster`.
+const DEFAULT_SHARDS: usize = 16;
+
+type Shard<S, R, C> = RwLock<HashMap<R, Room<S, R, C>>>;
+
+/// a broadcaster whose rooms are partitioned into independently locked shards by their id. Unlike `Arc<RwLock<Broadcaster>>`, there is no lock for the whole broadcaster: operations on the rooms of different shards never wait for each other, so a join in a room doesn't contend with a broadcast in another one. It's cheap to clone like `BroadcasterHandle`, and like it, it never holds a lock across an `.await`.
+///
+/// ```rust
+///
+/// use actix_wsb::ShardedBroadcaster;
+///
+/// fn main () {
+///     let broadcaster = ShardedBroadcaster::new();
+///
+///     // clone it for each worker, it's only an `Arc` under the hood:
+///     let worker_broadcaster = broadcaster.clone();
+///
+///     assert!(!worker_broadcaster.check(&"1".to_string()));
+/// }
+///
+/// ```
+pub struct ShardedBroadcaster<S = (), R = String, C = String> {
+    inner: Arc<Shards<S, R, C>>
+}
+
+impl<S, R, C> Clone for ShardedBroadcaster<S, R, C> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+struct Shards<S, R, C> {
+    shards: Vec<Shard<S, R, C>>,
+    hasher: RandomState,
+    events: Listeners<R, C>,
+    join_hooks: JoinHooks<S, R, C>,
+    index: Index<R, C>,
+    settings: RwLock<Settings>
+}
+
+/// the settings that the rooms which will be created get.
+#[derive(Clone)]
+pub(crate) struct Settings {
+    pub(crate) concurrency: Option<usize>,
+    pub(crate) queue_capacity: usize,
+    pub(crate) slow_consumer_policy: SlowConsumerPolicy,
+    pub(crate) duplicate_policy: DuplicatePolicy,
+    pub(crate) presence: bool,
+    pub(crate) fan_out: FanOut,
+    pub(crate) ids: Ids
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            concurrency: None,
+            queue_capacity: DEFAULT_CAPACITY,
+            slow_consumer_policy: SlowConsumerPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            presence: false,
+            fan_out: FanOut::Direct,
+            ids: Ids::default()
+        }
+    }
+}
+
+impl Settings {
+    /// creates a room with these settings, which emits to given listeners and updates given index.
+    pub(crate) fn room<S: Clone, R: Id, C: Id>(&self, id: R, events: &Listeners<R, C>, index: &Index<R, C>) -> Room<S, R, C> {
+        let mut room = Room::<S, R, C> {
+            id,
+            connectors: HashMap::new(),
+            events: events.clone(),
+            index: index.clone(),
+            concurrency: self.concurrency,
+            queue_capacity: self.queue_capacity,
+            slow_consumer_policy: self.slow_consumer_policy.clone(),
+            duplicate_policy: self.duplicate_policy.clone(),
+            presence: self.presence,
+            fan_out: FanOut::Direct,
+            channel: None,
+            key: room_key(),
+            graveyard: Graveyard::default()
+        };
+
+        room.set_fan_out(self.fan_out);
+
+        room
+    }
+}
+
+/// the write lock of the shard that a room is in, it derefs to that room. Other rooms of the same shard wait until it's dropped, so don't hold it across an `.await`.
+pub struct RoomGuard<'a, S = (), R = String, C = String> {
+    shard: RwLockWriteGuard<'a, HashMap<R, Room<S, R, C>>>,
+    id: R
+}
+
+impl<S, R: Id, C> Deref for RoomGuard<'_, S, R, C> {
+    type Target = Room<S, R, C>;
+
+    fn deref(&self) -> &Room<S, R, C> {
+        // the room can't be removed while it's shard is locked.
+        &self.shard[&self.id]
+    }
+}
+
+impl<S, R: Id, C> DerefMut for RoomGuard<'_, S, R, C> {
+    fn deref_mut(&mut self) -> &mut Room<S, R, C> {
+        self.shard.get_mut(&self.id).expect("room is removed while it's shard is locked")
+    }
+}
+
+impl<S, R, C> Default for ShardedBroadcaster<S, R, C> {
+    fn default() -> Self {
+        Self::typed_with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl ShardedBroadcaster {
+    /// creates a broadcaster with 16 shards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// creates a broadcaster with given count of shards, zero is treated as one. More shards means less contention but iterating all the rooms takes more locks.
+    pub fn with_shards(count: usize) -> Self {
+        Self::typed_with_shards(count)
+    }
+}
+
+impl<S, R, C> ShardedBroadcaster<S, R, C> {
+    /// creates a broadcaster with 16 shards whose connections have a state of type `S`, it's the equivalent of `Broadcaster::typed()`.
+    pub fn typed() -> Self {
+        Self::default()
+    }
+
+    /// creates a broadcaster with given count of shards whose connections have a state of type `S`.
+    pub fn typed_with_shards(count: usize) -> Self {
+        Self {
+            inner: Arc::new(Shards {
+                shards: (0..count.max(1)).map(|_| Shard::default()).collect(),
+                hasher: RandomState::new(),
+                events: Listeners::default(),
+                join_hooks: JoinHooks::default(),
+                index: Index::default(),
+                settings: RwLock::new(Settings::default())
+            })
+        }
+    }
+}
+
+impl<S: Clone, R: Id, C: Id> ShardedBroadcaster<S, R, C> {
+
+    /// adds the session to the room with given id as a connection with given id, creates the room if it's not exist. Only the shard of that room is locked. It's the equivalent of `Broadcaster::handle()`.
+    ///
+    ///```rust,ignore
+    ///
+    /// let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
+    ///
+    /// broadcaster.handle(&room_id, &id, session)?;
+    ///
+    ///```
+    pub fn handle<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), S::default(), None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the connection has given metadata. It's the equivalent of `Broadcaster::handle_with()`.
+    pub fn handle_with<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, metadata, S::default(), None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the connection has given state. It's the equivalent of `Broadcaster::handle_with_state()`.
+    pub fn handle_with_state<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), state, None)?;
+
+        Ok(())
+    }
+
+    /// same as `.handle()`, but the join hooks get the handshake request of the connection. It's the equivalent of `Broadcaster::handle_request()`.
+    pub fn handle_request<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// same as `.handle_request()`, but the connection has given metadata. It's the equivalent of `Broadcaster::handle_request_with()`.
+    pub fn handle_request_with<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata) -> Result<(), Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, metadata, S::default(), Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// same as `.handle_request()`, but the connection has given state. It's the equivalent of `Broadcaster::handle_request_with_state()`.
+    pub fn handle_request_with_state<Q, K>(&self, request: &HttpRequest, room_id: &Q, conn_id: &K, session: Session, state: S) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.admit(room_id, conn_id, session, Metadata::default(), state, Some(&Handshake::from(request)))?;
+
+        Ok(())
+    }
+
+    /// returns the room with given id, creates it if it's not exist. The shard of the room stays locked until the returned guard is dropped.
+    pub fn handle_room<Q>(&self, id: &Q) -> Result<RoomGuard<'_, S, R, C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let mut shard = self.inner.shard(id).write()?;
+
+        if !shard.contains_key(id) {
+            let room = self.inner.settings.read()?.room(id.to_owned(), &self.inner.events, &self.inner.index);
+
+            shard.insert(id.to_owned(), room);
+            self.inner.events.emit(Event::RoomCreated { room: id.to_owned() });
+        }
+
+        Ok(RoomGuard { shard, id: id.to_owned() })
+    }
+
+    /// returns the room with given id, or `Error::RoomNotFound` if it's not exist. The shard of the room stays locked until the returned guard is dropped.
+    ///
+    ///```rust,ignore
+    ///
+    /// if let Some(connection) = broadcaster.room(&room_id)?.check_connection(&id) {
+    ///     println!("{} messages waiting", connection.queued());
+    /// }
+    ///
+    ///```
+    pub fn room<Q>(&self, id: &Q) -> Result<RoomGuard<'_, S, R, C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let shard = self.inner.shard(id).write()?;
+        let id = shard.get_key_value(id).map(|(key, _)| key.clone()).ok_or_else(|| Error::RoomNotFound(id.to_string()))?;
+
+        Ok(RoomGuard { shard, id })
+    }
+
+    /// returns the room with given id if it's exist. The shard of the room stays locked until the returned guard is dropped.
+    pub fn check_room<Q>(&self, id: &Q) -> Option<RoomGuard<'_, S, R, C>> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.room(id).ok()
+    }
+
+    /// checks if a room with given id exist.
+    pub fn check<Q>(&self, id: &Q) -> bool where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        self.inner.shard(id).read().map(|shard| shard.contains_key(id)).unwrap_or(false)
+    }
+
+    /// registers a listener that will be called for every event that the rooms emit, like `.on_event()` method of `Broadcaster`.
+    pub fn on_event<F>(&self, listener: F) where F: Fn(&Event<R, C>) + Send + Sync + 'static {
+        self.inner.events.push(listener);
+    }
+
+    /// registers a hook that decides if a connection can join a room with the `.handle()`, `.join()` or `.move_connection()` methods, like `.on_join()` method of `Broadcaster`. Hooks are called under the lock of the shard of the room.
+    pub fn on_join<F>(&self, hook: F) where F: Fn(&JoinRequest<S, R, C>) -> Result<(), CloseReason> + Send + Sync + 'static {
+        self.inner.join_hooks.push(hook);
+    }
+
+    /// sets how many connections of a room can be sent to at the same time, for every room that exist and will be created.
+    pub fn set_concurrency(&self, limit: Option<usize>) -> Result<(), Error> {
+        self.inner.settings.write()?.concurrency = limit;
+
+        self.inner.each_shard_mut(|room| room.set_concurrency(limit))
+    }
+
+    /// sets how many messages the outbound queue of a connection can hold, for the rooms that exist and will be created. It affects the connections that will be added after that.
+    pub fn set_queue_capacity(&self, capacity: usize) -> Result<(), Error> {
+        self.inner.settings.write()?.queue_capacity = capacity;
+
+        self.inner.each_shard_mut(|room| room.set_queue_capacity(capacity))
+    }
+
+    /// sets what happens when the outbound queue of a connection is full, for the rooms that exist and will be created. It affects the connections that will be added after that.
+    pub fn set_slow_consumer_policy(&self, policy: SlowConsumerPolicy) -> Result<(), Error> {
+        self.inner.settings.write()?.slow_consumer_policy = policy.clone();
+
+        self.inner.each_shard_mut(|room| room.set_slow_consumer_policy(policy.clone()))
+    }
+
+    /// sets what happens when a session is added with the id of a connection that's already in it's room, for the rooms that exist and will be created.
+    pub fn set_duplicate_policy(&self, policy: DuplicatePolicy) -> Result<(), Error> {
+        self.inner.settings.write()?.duplicate_policy = policy.clone();
+
+        self.inner.each_shard_mut(|room| room.set_duplicate_policy(policy.clone()))
+    }
+
+    /// enables or disables the presence of the rooms that exist and will be created, like `.set_presence()` method of `Broadcaster`.
+    pub fn set_presence(&self, enabled: bool) -> Result<(), Error> {
+        self.inner.settings.write()?.presence = enabled;
+
+        self.inner.each_shard_mut(|room| room.set_presence(enabled))
+    }
+
+    /// sets how a room sends a message to it's connections, for the rooms that exist and will be created.
+    pub fn set_fan_out(&self, fan_out: FanOut) -> Result<(), Error> {
+        self.inner.settings.write()?.fan_out = fan_out;
+
+        self.inner.each_shard_mut(|room| room.set_fan_out(fan_out))
+    }
+
+    /// sets how the broadcaster generates the id's of the connections that added with the `.handle_generated()` methods, default is `IdGenerator::UuidV4`.
+    pub fn set_id_generator(&self, generator: IdGenerator) -> Result<(), Error> {
+        self.inner.settings.write()?.ids.generator = generator;
+
+        Ok(())
+    }
+
+    /// iterates through every room immutably, one shard at a time.
+    pub fn each_room_immut<F>(&self, f: F) -> Result<(), Error> where F: Fn(&Room<S, R, C>) {
+        for shard in &self.inner.shards {
+            for room in shard.read()?.values() {
+                f(room);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// starts the heartbeat supervisor, it's the equivalent of `Broadcaster::heartbeat()`. The supervisor stops when every clone of the broadcaster is dropped.
+    pub fn heartbeat(&self, config: HeartbeatConfig) -> JoinHandle<()> where S: Send + Sync + 'static {
+        actix_web::rt::spawn(heartbeat::supervise(Arc::downgrade(&self.inner), config))
+    }
+
+    /// records that a pong with given payload is received from the connection with given id, in any room. It's the equivalent of `.record_pong()` method of `Broadcaster`.
+    pub fn record_pong<K>(&self, conn_id: &K, bytes: &[u8]) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            let shard = self.inner.shard(&room_id).read()?;
+
+            if let Some(connection) = shard.get(&room_id).and_then(|room| room.connectors.get(conn_id)) {
+                connection.record_pong(bytes);
+
+                found = true;
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    /// changes the metadata of the connection with given id in every room that it's in, locking the shards of those rooms one by one. It's the equivalent of `.update_metadata()` method of `Broadcaster`.
+    pub fn update_metadata<F, K>(&self, conn_id: &K, mut f: F) -> Result<(), Error> where F: FnMut(&mut Metadata), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(room) = self.inner.shard(&room_id).write()?.get_mut(&room_id) {
+                found |= room.update_metadata(conn_id, &mut f).is_ok();
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    /// changes the state of the connection with given id in every room that it's in, locking the shards of those rooms one by one. It's the equivalent of `.update_state()` method of `Broadcaster`.
+    pub fn update_state<F, K>(&self, conn_id: &K, mut f: F) -> Result<(), Error> where F: FnMut(&mut S), C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut found = false;
+
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(room) = self.inner.shard(&room_id).write()?.get_mut(&room_id) {
+                found |= room.update_state(conn_id, &mut f).is_ok();
+            }
+        }
+
+        if found { Ok(()) } else { Err(Error::ConnectionNotFound(conn_id.to_string())) }
+    }
+
+    send_methods! {
+        receiver: [],
+        room: [room_id: Q],
+        generics: [Q],
+        bounds: [R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized],
+        condition: [],
+        output: Result<DeliveryReport<C>, Error>,
+        close: [async await] Result<DeliveryReport<C>, Error>,
+    }
+
+    /// closes the connection with given id and removes it from the room.
+    pub async fn close_conn<Q, K>(&self, room_id: &Q, reason: Option<CloseReason>, id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let closing = self.room(room_id)?.close_conn(reason, id);
+
+        closing.await
+    }
+
+    /// removes the room with given id and closes all of it's connections concurrently after releasing the lock of it's shard.
+    pub async fn remove_room<Q>(&self, room_id: &Q) -> Result<DeliveryReport<C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let mut room = self.inner.shard(room_id).write()?.remove(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+        let closing = room.close(None);
+        self.inner.events.emit(Event::RoomRemoved { room: room.id.clone() });
+
+        Ok(closing.await)
+    }
+
+    /// removes all empty rooms, one shard at a time, and emits an `Event::RoomRemoved` for each of them. The dead connections are pruned first, like `.remove_empty_rooms()` method of `Broadcaster`.
+    pub fn remove_empty_rooms(&self) -> Result<(), Error> {
+        let events = &self.inner.events;
+
+        for shard in &self.inner.shards {
+            shard.write()?.retain(|id, room| {
+                room.reap();
+
+                let keep = !room.connectors.is_empty();
+
+                if !keep {
+                    events.emit(Event::RoomRemoved { room: id.clone() });
+                }
+
+                keep
+            });
+        }
+
+        Ok(())
+    }
+
+    /// removes a connection from every room that it's in and returns the session of it, returns `Error::ConnectionNotFound` if there is no connection with given id. It's the equivalent of `.remove_connection()` method of `Broadcaster`, the session isn't closed.
+    pub fn remove_connection<K>(&self, id: &K) -> Result<Session, Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.take_all(id, Room::part)?.into_iter().next() {
+            Some(connection) => Ok(connection.session),
+            None => Err(Error::ConnectionNotFound(id.to_string()))
+        }
+    }
+
+    /// adds the connection with given id to the room with given id too, creates the room if it's not exist. It's the equivalent of `.join()` method of `Broadcaster`.
+    pub fn join<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let connection = self.find(conn_id)?.ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+        let mut room = self.handle_room(room_id)?;
+
+        room.welcome(&self.inner.join_hooks, &connection)?;
+        room.join_copy(&connection);
+
+        Ok(())
+    }
+
+    /// removes the connection with given id from the room with given id without closing it. It's the equivalent of `.leave()` method of `Broadcaster`.
+    pub fn leave<Q, K>(&self, room_id: &Q, conn_id: &K) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.room(room_id)?.part(conn_id).map(|_| ()).ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))
+    }
+
+    /// returns the id's of the rooms that the connection with given id is in.
+    pub fn rooms_of<K>(&self, conn_id: &K) -> Vec<R> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        self.inner.index.rooms_of(conn_id)
+    }
+
+    /// moves the connection with given id from a room to another one, holding the locks of the shards of both rooms, so no broadcast to them can happen in the middle of it. It's the equivalent of `.move_connection()` method of `Broadcaster`.
+    pub fn move_connection<K, Q>(&self, conn_id: &K, from: &Q, to: &Q) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let (source, target) = (self.inner.position(from), self.inner.position(to));
+
+        // the shards are locked in the order of their position, so two moves in opposite directions can't wait for each other forever.
+        let mut shards = vec![self.inner.shards[source.min(target)].write()?];
+
+        if source != target {
+            shards.push(self.inner.shards[source.max(target)].write()?);
+        }
+
+        let slot = |position: usize| usize::from(position != source.min(target));
+
+        let connection = shards[slot(source)].get_mut(from)
+                                             .ok_or_else(|| Error::RoomNotFound(from.to_string()))?
+                                             .check_connection(conn_id)
+                                             .ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+        let shard = &mut shards[slot(target)];
+
+        if !shard.contains_key(to) {
+            let room = self.inner.settings.read()?.room(to.to_owned(), &self.inner.events, &self.inner.index);
+
+            shard.insert(to.to_owned(), room);
+            self.inner.events.emit(Event::RoomCreated { room: to.to_owned() });
+        }
+
+        // the hooks decide before the connection leaves the source room, so a rejected move doesn't change anything.
+        if let Some(room) = shard.get(to) {
+            room.welcome(&self.inner.join_hooks, &connection)?;
+        }
+
+        let connection = shards[slot(source)].get_mut(from)
+                                             .and_then(|room| room.part(conn_id))
+                                             .ok_or_else(|| Error::ConnectionNotFound(conn_id.to_string()))?;
+
+        if let Some(room) = shards[slot(target)].get_mut(to) {
+            room.adopt(connection);
+        }
+
+        Ok(())
+    }
+
+    /// closes the connection with given id and removes it from every room that it's in, no shard is locked while the close frame is sent. It's the equivalent of `.disconnect()` method of `Broadcaster`.
+    pub async fn disconnect<K>(&self, reason: Option<CloseReason>, conn_id: &K) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let memberships = self.take_all(conn_id, Room::evict)?;
+
+        close_memberships(memberships, reason, conn_id).await
+    }
+
+    /// closes given session of the connection with given id, or the whole connection if that was it's last session. It's the equivalent of `.close_session()` method of `Broadcaster`.
+    pub async fn close_session<K>(&self, reason: Option<CloseReason>, conn_id: &K, session: Session) -> Result<(), Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        match self.find(conn_id)? {
+            Some(connection) if connection.outbound.release() => close_released(session, reason).await,
+            Some(_) => self.disconnect(reason, conn_id).await,
+            None => Err(Error::ConnectionNotFound(conn_id.to_string()))
+        }
+    }
+
+    /// adds the connection to the room with given id through the join hooks, holding the lock of it's shard. Creates the room if it's not exist.
+    fn admit<Q, K>(&self, room_id: &Q, conn_id: &K, session: Session, metadata: Metadata, state: S, handshake: Option<&Handshake>) -> Result<(), Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized, C: Borrow<K>, K: Hash + Eq + Display + ToOwned<Owned = C> + ?Sized {
+        self.handle_room(room_id)?.admit(&self.inner.join_hooks, conn_id, session, metadata, state, handshake)
+    }
+
+    /// returns a copy of the connection with given id from one of it's rooms, locking their shards one by one.
+    fn find<K>(&self, conn_id: &K) -> Result<Option<Connection<S, C>>, Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(connection) = self.inner.shard(&room_id).read()?.get(&room_id).and_then(|room| room.connectors.get(conn_id)) {
+                return Ok(Some(connection.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// removes the connection with given id from every room that it's in with given method of the room and returns them, locking their shards one by one.
+    fn take_all<K>(&self, conn_id: &K, remove: Removal<S, R, C, K>) -> Result<Vec<Connection<S, C>>, Error> where C: Borrow<K>, K: Hash + Eq + Display + ?Sized {
+        let mut memberships = vec![];
+
+        for room_id in self.inner.index.rooms_of(conn_id) {
+            if let Some(connection) = self.inner.shard(&room_id).write()?.get_mut(&room_id).and_then(|room| remove(room, conn_id)) {
+                memberships.push(connection);
+            }
+        }
+
+        Ok(memberships)
+    }
+
+    /// publishes the message to the channel of the room under the read lock of it's shard if it has one, otherwise sends it to every connector like `.dispatch()`. The connectors whose writer task is stopped are pruned under the write lock of the shard after publishing.
+    async fn send_all<Q>(&self, room_id: &Q, message: Message) -> Result<DeliveryReport<C>, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let (published, dead) = {
+            let shard = self.inner.shard(room_id).read()?;
+            let room = shard.get(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+            (room.publish(message), room.has_dead())
+        };
+
+        match published {
+            Ok(mut report) => {
+                if dead {
+                    if let Some(mut room) = self.check_room(room_id) {
+                        report.pruned = room.reap();
+                    }
+                }
+
+                Ok(report)
+            },
+            Err(message) => self.dispatch(room_id, message, |_| true).await
+        }
+    }
+
+    /// copies the targets of the room under the read lock of it's shard, sends the message to them without holding any lock and then prunes the closed ones.
+    async fn dispatch<F, Q>(&self, room_id: &Q, message: Message, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let mut snapshot = self.snapshot(room_id, condition)?;
+        let mut report = DeliveryReport::<C> { skipped: snapshot.skipped, ..Default::default() };
+
+        fan_out(&mut snapshot.targets, &message, snapshot.concurrency, &mut report).await;
+
+        if !report.pruned.is_empty() || snapshot.dead {
+            if let Some(mut room) = self.check_room(room_id) {
+                room.settle(&mut report);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// removes the connections to close from the room under the write lock of it's shard and closes them after releasing it.
+    async fn close_where<F, Q>(&self, room_id: &Q, reason: Option<CloseReason>, condition: F) -> Result<DeliveryReport<C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let closing = self.room(room_id)?.close_where(reason, condition);
+
+        Ok(closing.await)
+    }
+
+    /// copies the connections of the room that satisfies the condition under the read lock of it's shard.
+    fn snapshot<F, Q>(&self, room_id: &Q, condition: F) -> Result<Snapshot<S, C>, Error> where F: Fn(&Connection<S, C>) -> bool, R: Borrow<Q>, Q: Hash + Eq + Display + ?Sized {
+        let shard = self.inner.shard(room_id).read()?;
+        let room = shard.get(room_id).ok_or_else(|| Error::RoomNotFound(room_id.to_string()))?;
+
+        Ok(Snapshot::of(room, condition))
+    }
+}
+
+impl<S: Clone, R: Id> ShardedBroadcaster<S, R, String> {
+    /// same as `.handle()`, but the broadcaster generates a unique id for the connection and returns it. It's the equivalent of `Broadcaster::handle_generated()`.
+    pub fn handle_generated<Q>(&self, room_id: &Q, session: Session) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        self.handle_generated_with_state(room_id, session, S::default())
+    }
+
+    /// same as `.handle_generated()`, but the connection has given metadata.
+    pub fn handle_generated_with<Q>(&self, room_id: &Q, session: Session, metadata: Metadata) -> Result<String, Error> where S: Default, R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let id = self.inner.settings.read()?.ids.next();
+
+        self.handle_with(room_id, &id, session, metadata)?;
+
+        Ok(id)
+    }
+
+    /// same as `.handle_generated()`, but the connection has given state.
+    pub fn handle_generated_with_state<Q>(&self, room_id: &Q, session: Session, state: S) -> Result<String, Error> where R: Borrow<Q>, Q: Hash + Eq + Display + ToOwned<Owned = R> + ?Sized {
+        let id = self.inner.settings.read()?.ids.next();
+
+        self.handle_with_state(room_id, &id, session, state)?;
+
+        Ok(id)
+    }
+}
+
+impl<S, R: Id, C> Shards<S, R, C> {
+    /// the shard that the room with given id belongs to.
+    fn shard<Q: Hash + ?Sized>(&self, room_id: &Q) -> &Shard<S, R, C> {
+        &self.shards[self.position(room_id)]
+    }
+
+    /// the position of the shard that the room with given id belongs to.
+    fn position<Q: Hash + ?Sized>(&self, room_id: &Q) -> usize {
+        self.hasher.hash_one(room_id) as usize % self.shards.len()
+    }
+
+    fn each_shard_mut<F>(&self, f: F) -> Result<(), Error> where F: Fn(&mut Room<S, R, C>) {
+        for shard in &self.shards {
+            for room in shard.write()?.values_mut() {
+                f(room);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static, R: Id, C: Id> Supervised for Shards<S, R, C> {
+    type State = S;
+    type RoomId = R;
+    type ConnId = C;
+
+    fn heartbeat_targets(&self, max_missed: u32) -> Option<Targets<S, R, C>> {
+        let mut targets = Targets::default();
+
+        for shard in &self.shards {
+            collect_targets(shard.read().ok()?.values(), max_missed, &mut targets);
+        }
+
+        Some(targets)
+    }
+
+    fn expire(&self, expired: &[Expired<S, R, C>], reason: &Option<CloseReason>) -> bool {
+        for (room_id, connection) in expired {
+            let Ok(mut shard) = self.shard(room_id).write() else { return false };
+
+            if let Some(room) = shard.get_mut(room_id) {
+                room.time_out(connection, reason);
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,117 @@
+// the room-wide send methods are the same on `Room`, `BroadcasterHandle`, `ShardedBroadcaster` and `RoomHandle`, only the receiver, the room id argument, the return type and the bounds of the condition differ. They're generated by that macro over the `send_all`, `dispatch` and `close_where` methods of each type, so they can't drift apart.
+
+/// generates `.broadcast()`, `.ping()`, `.pong()`, `.binary()`, `.continuation()`, their `_if` and `_if_not` variants and `.close()`, `.close_if()` and `.close_if_not()` methods.
+///
+/// - `receiver`: `[mut]` for `&mut self` methods, `[]` for `&self` ones.
+/// - `room`: the room id argument and it's type, like `[room_id: Q]`, or `[]` if the type is a single room.
+/// - `generics` and `bounds`: the generic parameters and the where clause that the room id argument needs.
+/// - `condition`: the bounds of the condition closures in addition to `Fn(&Connection<S, C>) -> bool`.
+/// - `output`: the return type of the send methods.
+/// - `close`: `[async await]` and the return type of the close methods if `close_where` is async, or `[]` and the type of the future that it returns.
+///
+/// The attributes after them are added to the docs of `.broadcast()`.
+macro_rules! send_methods {
+    (
+        receiver: [$($mutability:tt)?],
+        room: [$($room:ident: $room_type:ident)?],
+        generics: [$($generic:ident),*],
+        bounds: [$($bound:tt)*],
+        condition: [$($condition:tt)*],
+        output: $output:ty,
+        close: [$($close_async:ident $close_await:ident)?] $close_output:ty,
+        $(#[$broadcast:meta])*
+    ) => {
+        /// broadcastes the message to all connectors of the room.
+        $(#[$broadcast])*
+        pub async fn broadcast<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? message: impl Into<$crate::ByteString>) -> $output where $($bound)* {
+            self.send_all($($room,)? actix_ws::Message::Text(message.into())).await
+        }
+
+        /// broadcastes the message to the connectors of the room if given condition for connection instances is true.
+        pub async fn broadcast_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? message: impl Into<$crate::ByteString>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Text(message.into()), condition).await
+        }
+
+        /// broadcastes the message to the connectors of the room if given condition for connection instances is false.
+        pub async fn broadcast_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? message: impl Into<$crate::ByteString>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Text(message.into()), move |connection| !condition(connection)).await
+        }
+
+        /// broadcastes the ping to all connectors of the room.
+        pub async fn ping<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>) -> $output where $($bound)* {
+            self.send_all($($room,)? actix_ws::Message::Ping(bytes.into())).await
+        }
+
+        /// broadcastes the ping to the connectors of the room if given condition for connection instances is true.
+        pub async fn ping_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Ping(bytes.into()), condition).await
+        }
+
+        /// broadcastes the ping to the connectors of the room if given condition for connection instances is false.
+        pub async fn ping_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Ping(bytes.into()), move |connection| !condition(connection)).await
+        }
+
+        /// broadcastes the pong to all connectors of the room.
+        pub async fn pong<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>) -> $output where $($bound)* {
+            self.send_all($($room,)? actix_ws::Message::Pong(bytes.into())).await
+        }
+
+        /// broadcastes the pong to the connectors of the room if given condition for connection instances is true.
+        pub async fn pong_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Pong(bytes.into()), condition).await
+        }
+
+        /// broadcastes the pong to the connectors of the room if given condition for connection instances is false.
+        pub async fn pong_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Pong(bytes.into()), move |connection| !condition(connection)).await
+        }
+
+        /// broadcastes the raw binary bytes to all connectors of the room.
+        pub async fn binary<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>) -> $output where $($bound)* {
+            self.send_all($($room,)? actix_ws::Message::Binary(bytes.into())).await
+        }
+
+        /// broadcastes the raw binary bytes to the connectors of the room if given condition for connection instances is true.
+        pub async fn binary_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Binary(bytes.into()), condition).await
+        }
+
+        /// broadcastes the raw binary bytes to the connectors of the room if given condition for connection instances is false.
+        pub async fn binary_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? bytes: impl Into<actix_web::web::Bytes>, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Binary(bytes.into()), move |connection| !condition(connection)).await
+        }
+
+        /// broadcastes the continuation message to all connectors of the room.
+        pub async fn continuation<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? item: actix_ws::Item) -> $output where $($bound)* {
+            self.send_all($($room,)? actix_ws::Message::Continuation(item)).await
+        }
+
+        /// broadcastes the continuation message to the connectors of the room if given condition for connection instances is true.
+        pub async fn continuation_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? item: actix_ws::Item, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Continuation(item), condition).await
+        }
+
+        /// broadcastes the continuation message to the connectors of the room if given condition for connection instances is false.
+        pub async fn continuation_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? item: actix_ws::Item, condition: F) -> $output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.dispatch($($room,)? actix_ws::Message::Continuation(item), move |connection| !condition(connection)).await
+        }
+
+        /// closes all the connections of the room concurrently and removes them from it, the room stays open. The `DeliveryReport` of it lists the connections that their close frame is written to the session in `delivered` and the ones that failed in `failed`, all of them are removed either way. They're removed from the room before their close frames are sent.
+        pub $($close_async)? fn close<$($generic),*>(&$($mutability)? self, $($room: &$room_type,)? reason: Option<actix_ws::CloseReason>) -> $close_output where $($bound)* {
+            self.close_where($($room,)? reason, |_| true)$(.$close_await)?
+        }
+
+        /// closes the connections of the room if given condition for connection instances is true and removes them from it.
+        pub $($close_async)? fn close_if<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? reason: Option<actix_ws::CloseReason>, condition: F) -> $close_output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.close_where($($room,)? reason, condition)$(.$close_await)?
+        }
+
+        /// closes the connections of the room if given condition for connection instances is false and removes them from it.
+        pub $($close_async)? fn close_if_not<F, $($generic),*>(&$($mutability)? self, $($room: &$room_type,)? reason: Option<actix_ws::CloseReason>, condition: F) -> $close_output where F: Fn(&$crate::Connection<S, C>) -> bool $($condition)*, $($bound)* {
+            self.close_where($($room,)? reason, move |connection| !condition(connection))$(.$close_await)?
+        }
+    };
+}
+
+pub(crate) use send_methods;
@@ -0,0 +1,135 @@
+//! rooms that run as their own task and the router of them.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use actix_web::rt::time::timeout;
+use actix_wsb::{BroadcasterHandle, Error, Event, Room, RoomHandle, RoomRouter, ShardedBroadcaster};
+use common::{connect, numbered, settle};
+
+#[actix_web::test]
+async fn messages_from_concurrent_senders_reach_every_connection_in_the_same_order() {
+    let room = RoomHandle::spawn(Room::create("room".to_string()));
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    room.join("a", a).await.unwrap();
+    room.join("b", b).await.unwrap();
+
+    let senders = ["x", "y", "z"].map(|sender| {
+        let room = room.clone();
+
+        async move {
+            for i in 0..10 {
+                let _ = room.broadcast(format!("{}{}", sender, i)).await.unwrap();
+            }
+        }
+    });
+
+    futures_util::future::join_all(senders).await;
+
+    let received = first.texts().await;
+
+    assert_eq!(received.len(), 30);
+    assert_eq!(second.texts().await, received);
+}
+
+#[actix_web::test]
+async fn stalled_close_doesnt_stop_the_mailbox() {
+    let room = RoomHandle::spawn(Room::create("room".to_string()));
+    let (a, _stalled) = connect().await;
+    let (b, mut other) = connect().await;
+
+    room.join("a", a).await.unwrap();
+    room.join("b", b).await.unwrap();
+
+    // more than the session can buffer, so the writer task of "a" is stuck until it's client reads.
+    for message in numbered(40) {
+        let _ = room.broadcast_if(message, |connection| connection.id == "a").await.unwrap();
+    }
+
+    settle().await;
+
+    let closing = {
+        let room = room.clone();
+
+        actix_web::rt::spawn(async move { room.close_conn(None, "a").await })
+    };
+
+    settle().await;
+
+    let report = timeout(Duration::from_secs(1), room.broadcast("hi")).await.unwrap().unwrap();
+
+    assert_eq!(report.delivered, vec!["b".to_string()]);
+    assert_eq!(other.texts().await, vec!["hi".to_string()]);
+    assert_eq!(closing.await.unwrap(), Err(Error::Timeout));
+}
+
+#[actix_web::test]
+async fn join_that_reaches_a_stopped_room_spawns_it_again() {
+    let router = RoomRouter::new();
+    let events = Arc::new(Mutex::new(vec![]));
+    let (a, _first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    {
+        let events = Arc::clone(&events);
+        router.on_event(move |event| events.lock().unwrap().push(event.clone()));
+    }
+
+    router.handle("room", "a", a).await.unwrap();
+    router.leave("room", "a").await.unwrap();
+
+    // the room gets the shutdown before the join, so the join finds it stopped and spawns it again.
+    let (removed, joined) = futures_util::join!(router.remove_empty_rooms(), router.handle("room", "b", b));
+
+    removed.unwrap();
+    joined.unwrap();
+
+    let created = events.lock().unwrap().iter().filter(|event| matches!(event, Event::RoomCreated { .. })).count();
+
+    assert_eq!(created, 2);
+    assert_eq!(router.room("room").unwrap().broadcast("hi").await.unwrap().delivered, vec!["b".to_string()]);
+    assert_eq!(second.texts().await, vec!["hi".to_string()]);
+}
+
+#[actix_web::test]
+async fn rooms_whose_connections_are_all_closed_are_removed() {
+    let router = RoomRouter::new();
+    let (a, _client) = connect().await;
+
+    router.handle("personal", "a", a).await.unwrap();
+    router.join("team", "a").await.unwrap();
+
+    // "a" is closed in "personal", so it's membership in "team" is dead but not pruned yet.
+    router.room("personal").unwrap().close_conn(None, "a").await.unwrap();
+    settle().await;
+
+    router.remove_empty_rooms().await.unwrap();
+
+    assert_eq!(router.room_ids().unwrap(), Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn remove_empty_rooms_prunes_the_dead_connections_first() {
+    let broadcaster = BroadcasterHandle::new();
+    let sharded = ShardedBroadcaster::new();
+    let (a, _first) = connect().await;
+    let (b, _second) = connect().await;
+
+    broadcaster.handle("personal", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    sharded.handle("personal", "b", b).unwrap();
+    sharded.join("team", "b").unwrap();
+
+    broadcaster.close_conn("personal", None, "a").await.unwrap();
+    sharded.close_conn("personal", None, "b").await.unwrap();
+    settle().await;
+
+    broadcaster.write().unwrap().remove_empty_rooms();
+    sharded.remove_empty_rooms().unwrap();
+
+    assert!(broadcaster.write().unwrap().check_room("team").is_none());
+    assert!(sharded.check_room("team").is_none());
+}
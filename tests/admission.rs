@@ -0,0 +1,79 @@
+//! the join hooks that decide if a connection can join a room.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use actix_ws::{CloseCode, CloseReason};
+use actix_wsb::{Error, JoinRequest, ShardedBroadcaster};
+use common::{connect, connect_with, recorded, request};
+
+#[actix_web::test]
+async fn rejected_move_doesnt_change_the_rooms() {
+    let (broadcaster, _) = recorded();
+    let (a, _client) = connect().await;
+
+    broadcaster.handle("lobby", "a", a).unwrap();
+    broadcaster.read().unwrap().on_join(|request| match request.room.as_str() {
+        "vip" => Err(CloseCode::Policy.into()),
+        _ => Ok(())
+    });
+
+    assert_eq!(broadcaster.move_connection("a", "lobby", "vip"), Err(Error::Rejected(CloseCode::Policy.into())));
+    assert_eq!(broadcaster.join("vip", "a"), Err(Error::Rejected(CloseCode::Policy.into())));
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), vec!["lobby".to_string()]);
+
+    let report = broadcaster.broadcast("lobby", "still here").await.unwrap();
+
+    assert_eq!(report.delivered, vec!["a".to_string()]);
+}
+
+/// records the room and the `x-user` header of the handshake of every join that the hooks see.
+type Seen = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+fn seen() -> (Seen, impl Fn(&JoinRequest) -> Result<(), CloseReason> + Send + Sync + 'static) {
+    let seen = Seen::default();
+    let recorded = Arc::clone(&seen);
+
+    let hook = move |request: &JoinRequest| {
+        let user = request.handshake.and_then(|handshake| handshake.headers.get("x-user")).map(|value| value.to_str().unwrap().to_string());
+        recorded.lock().unwrap().push((request.room.clone(), user));
+
+        Ok(())
+    };
+
+    (seen, hook)
+}
+
+fn expected() -> Vec<(String, Option<String>)> {
+    ["lobby", "team", "vip"].map(|room| (room.to_string(), Some("42".to_string()))).to_vec()
+}
+
+#[actix_web::test]
+async fn hooks_get_the_handshake_on_join_and_move() {
+    let (broadcaster, _) = recorded();
+    let (a, _client, req) = connect_with(request().insert_header(("x-user", "42"))).await;
+    let (seen, hook) = seen();
+
+    broadcaster.read().unwrap().on_join(hook);
+
+    broadcaster.handle_request(&req, "lobby", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    broadcaster.move_connection("a", "lobby", "vip").unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), expected());
+}
+
+#[actix_web::test]
+async fn sharded_hooks_get_the_handshake_on_join_and_move() {
+    let broadcaster = ShardedBroadcaster::new();
+    let (a, _client, req) = connect_with(request().insert_header(("x-user", "42"))).await;
+    let (seen, hook) = seen();
+
+    broadcaster.on_join(hook);
+
+    broadcaster.handle_request(&req, "lobby", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    broadcaster.move_connection("a", "lobby", "vip").unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), expected());
+}
@@ -0,0 +1,72 @@
+//! the rooms whose fan out is `FanOut::Channel`.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, Event, FanOut};
+use common::{connect, numbered, recorded, settle};
+
+#[actix_web::test]
+async fn closed_sessions_of_channel_rooms_are_pruned() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _first) = connect().await;
+    let (b, second) = connect().await;
+
+    broadcaster.write().unwrap().set_fan_out(FanOut::Channel(16));
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    drop(second);
+    let _ = broadcaster.broadcast("room", "first").await.unwrap();
+    settle().await;
+
+    let report = broadcaster.broadcast("room", "second").await.unwrap();
+
+    assert_eq!(report.pruned, vec!["b".to_string()]);
+    assert_eq!(report.subscribers, 1);
+    assert_eq!(broadcaster.rooms_of("b").unwrap(), Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn messages_are_published_once_and_conditional_sends_go_one_by_one() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    broadcaster.write().unwrap().set_fan_out(FanOut::Channel(16));
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    let published = broadcaster.broadcast("room", "everyone").await.unwrap();
+
+    assert_eq!((published.subscribers, published.delivered.len()), (2, 0));
+    assert_eq!(first.texts().await, vec!["everyone".to_string()]);
+    assert_eq!(second.texts().await, vec!["everyone".to_string()]);
+
+    let direct = broadcaster.broadcast_if("room", "only a", |connection| connection.id == "a").await.unwrap();
+
+    assert_eq!((direct.subscribers, direct.delivered), (0, vec!["a".to_string()]));
+    assert_eq!(first.texts().await, vec!["only a".to_string()]);
+    assert!(second.texts().await.is_empty());
+}
+
+#[actix_web::test]
+async fn connections_that_fall_behind_the_channel_lose_the_oldest_messages() {
+    let (broadcaster, events) = recorded();
+    let (a, mut client) = connect().await;
+
+    broadcaster.write().unwrap().set_fan_out(FanOut::Channel(2));
+    broadcaster.handle("room", "a", a).unwrap();
+
+    // the writer task doesn't run until the test yields, so the channel holds only the last two of them.
+    for message in numbered(10) {
+        let _ = broadcaster.broadcast("room", message).await.unwrap();
+    }
+
+    settle().await;
+
+    let lagged = broadcaster.write().unwrap().room("room").unwrap().check_connection("a").unwrap().lagged();
+
+    assert_eq!(lagged, 8);
+    assert_eq!(client.texts().await, vec!["m8".to_string(), "m9".to_string()]);
+    assert!(events.lock().unwrap().contains(&Event::Lagged { room: "room".to_string(), connection: "a".to_string(), missed: 8 }));
+}
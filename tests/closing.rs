@@ -0,0 +1,59 @@
+//! closing the connections of a broadcaster after it's lock is released.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, Error};
+use common::{connect, numbered, settle, Frame};
+
+#[actix_web::test]
+async fn stalled_client_doesnt_hold_the_lock_while_it_is_closed() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _stalled) = connect().await;
+    let (b, mut other) = connect().await;
+
+    broadcaster.handle("stalled", "a", a).unwrap();
+    broadcaster.handle("other", "b", b).unwrap();
+
+    // more than the session can buffer, so the writer task of "a" is stuck until it's client reads.
+    for message in numbered(40) {
+        let _ = broadcaster.broadcast("stalled", message).await;
+    }
+
+    settle().await;
+
+    let closing = broadcaster.write().unwrap().remove_room("stalled");
+    let pending = actix_web::rt::spawn(closing);
+
+    settle().await;
+
+    // the room is removed before the close frame is sent and the lock is free while it's pending.
+    assert!(broadcaster.rooms_of("a").unwrap().is_empty());
+    assert_eq!(broadcaster.broadcast("other", "hi").await.unwrap().delivered, vec!["b".to_string()]);
+    assert_eq!(other.texts().await, vec!["hi".to_string()]);
+
+    let report = pending.await.unwrap().unwrap();
+
+    assert_eq!(report.failed, vec![("a".to_string(), Error::Timeout)]);
+}
+
+#[actix_web::test]
+async fn closing_removes_the_connections_before_awaiting_it() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    let closing = broadcaster.write().unwrap().room("room").unwrap().close_if(None, |connection| connection.id == "a");
+
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), Vec::<String>::new());
+    assert_eq!(broadcaster.rooms_of("b").unwrap(), vec!["room".to_string()]);
+
+    let report = closing.await;
+
+    assert_eq!(report.delivered, vec!["a".to_string()]);
+    assert_eq!(report.skipped, vec!["b".to_string()]);
+    assert_eq!(first.frames().await, vec![Frame::Close(None)]);
+    assert!(second.frames().await.is_empty());
+}
@@ -0,0 +1,145 @@
+//! helpers that the integration tests share. Every test file uses a different part of them.
+
+#![allow(dead_code)]
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::rt::time::timeout;
+use actix_web::{test::TestRequest, web, FromRequest, HttpRequest};
+use actix_ws::{MessageStream, Session};
+use actix_wsb::{Broadcaster, BroadcasterHandle, Event};
+
+/// how long a client waits for the next chunk before it decides that nothing else is coming.
+const IDLE: Duration = Duration::from_millis(100);
+
+/// a frame that the client received from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// the close code, if the frame has one.
+    Close(Option<u16>),
+    Other(u8)
+}
+
+/// the client side of a websocket session that's created from a test request. It doesn't read anything until it's asked to, so it acts like a client that can't keep up until then.
+pub struct Client {
+    body: BoxBody,
+    buffer: Vec<u8>,
+    ended: bool,
+    _stream: MessageStream
+}
+
+/// the handshake request of the test sessions.
+pub fn request() -> TestRequest {
+    TestRequest::get().insert_header(("upgrade", "websocket"))
+                      .insert_header(("connection", "upgrade"))
+                      .insert_header(("sec-websocket-version", "13"))
+                      .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+}
+
+/// creates a websocket session and the client of it.
+pub async fn connect() -> (Session, Client) {
+    let (session, client, _) = connect_with(request()).await;
+
+    (session, client)
+}
+
+/// creates a websocket session from given handshake request and returns the request too, for the `.handle_request()` methods.
+pub async fn connect_with(request: TestRequest) -> (Session, Client, HttpRequest) {
+    let (req, mut payload) = request.to_http_parts();
+
+    let body = web::Payload::from_request(&req, &mut payload).await.unwrap();
+    let (response, session, stream) = actix_ws::handle(&req, body).unwrap();
+
+    (session, Client { body: response.into_body(), buffer: vec![], ended: false, _stream: stream }, req)
+}
+
+impl Client {
+    /// reads the frames that the server sent until nothing arrives for a while or the session ends.
+    pub async fn frames(&mut self) -> Vec<Frame> {
+        while !self.ended {
+            match timeout(IDLE, futures_util::future::poll_fn(|cx| Pin::new(&mut self.body).poll_next(cx))).await {
+                Ok(Some(Ok(chunk))) => self.buffer.extend_from_slice(&chunk),
+                Ok(_) => self.ended = true,
+                Err(_) => break
+            }
+        }
+
+        let mut frames = vec![];
+
+        while let Some((frame, length)) = parse(&self.buffer) {
+            frames.push(frame);
+            self.buffer.drain(..length);
+        }
+
+        frames
+    }
+
+    /// the text frames of `.frames()`.
+    pub async fn texts(&mut self) -> Vec<String> {
+        self.frames().await.into_iter().filter_map(|frame| match frame {
+            Frame::Text(text) => Some(text),
+            _ => None
+        }).collect()
+    }
+
+    /// returns true if the response ended, because every clone of the session is dropped or closed. It's known after `.frames()` reads the end.
+    pub fn ended(&self) -> bool {
+        self.ended
+    }
+}
+
+/// parses the first frame of the buffer and returns it with it's length, or `None` if the buffer doesn't have a whole frame. The frames of the server aren't masked.
+fn parse(buffer: &[u8]) -> Option<(Frame, usize)> {
+    let header = buffer.get(..2)?;
+    let (length, offset) = match header[1] & 0x7f {
+        126 => (u16::from_be_bytes([*buffer.get(2)?, *buffer.get(3)?]) as usize, 4),
+        127 => (u64::from_be_bytes(buffer.get(2..10)?.try_into().ok()?) as usize, 10),
+        length => (length as usize, 2)
+    };
+
+    let payload = buffer.get(offset..offset + length)?.to_vec();
+    let frame = match header[0] & 0x0f {
+        0x1 => Frame::Text(String::from_utf8(payload).unwrap()),
+        0x2 => Frame::Binary(payload),
+        0x8 => Frame::Close(payload.get(..2).map(|code| u16::from_be_bytes([code[0], code[1]]))),
+        0x9 => Frame::Ping(payload),
+        0xa => Frame::Pong(payload),
+        opcode => Frame::Other(opcode)
+    };
+
+    Some((frame, offset + length))
+}
+
+/// records the events of the broadcaster.
+pub fn record<S: Clone>(broadcaster: &Arc<RwLock<Broadcaster<S>>>) -> Arc<Mutex<Vec<Event>>> {
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = Arc::clone(&events);
+
+    broadcaster.read().unwrap().on_event(move |event| recorded.lock().unwrap().push(event.clone()));
+
+    events
+}
+
+/// a broadcaster that records it's events, and the handle of it for the methods that await.
+pub fn recorded() -> (BroadcasterHandle, Arc<Mutex<Vec<Event>>>) {
+    let broadcaster = Broadcaster::new();
+    let events = record(&broadcaster);
+
+    (BroadcasterHandle::from(broadcaster), events)
+}
+
+/// lets the writer tasks run for a while.
+pub async fn settle() {
+    actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+}
+
+/// the texts from 0 to given count, like "m0", "m1".
+pub fn numbered(count: usize) -> Vec<String> {
+    (0..count).map(|number| format!("m{number}")).collect()
+}
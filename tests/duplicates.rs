@@ -0,0 +1,73 @@
+//! the duplicate policies, when a session is added with the id of a connection that's already in the room.
+
+mod common;
+
+use actix_ws::{CloseCode, CloseReason};
+use actix_wsb::{BroadcasterHandle, DuplicatePolicy, Error};
+use common::{connect, Frame};
+
+#[actix_web::test]
+async fn reject_closes_the_new_session() {
+    let broadcaster = BroadcasterHandle::new();
+    let (first, mut old) = connect().await;
+    let (second, mut new) = connect().await;
+
+    broadcaster.write().unwrap().set_duplicate_policy(DuplicatePolicy::Reject(Some(CloseReason { code: CloseCode::Policy, description: None })));
+    broadcaster.handle("room", "a", first).unwrap();
+
+    assert_eq!(broadcaster.handle("room", "a", second).err(), Some(Error::Duplicate("a".to_string())));
+
+    let _ = broadcaster.broadcast("room", "hello").await.unwrap();
+
+    assert_eq!(new.frames().await, vec![Frame::Close(Some(1008))]);
+    assert_eq!(old.texts().await, vec!["hello".to_string()]);
+}
+
+#[actix_web::test]
+async fn replace_closes_the_old_session_by_default() {
+    let broadcaster = BroadcasterHandle::new();
+    let (first, mut old) = connect().await;
+    let (second, mut new) = connect().await;
+
+    broadcaster.handle("room", "a", first).unwrap();
+    broadcaster.handle("room", "a", second).unwrap();
+
+    let report = broadcaster.broadcast("room", "hello").await.unwrap();
+
+    assert_eq!(report.delivered, vec!["a".to_string()]);
+    assert_eq!(old.frames().await, vec![Frame::Close(Some(1000))]);
+    assert_eq!(new.texts().await, vec!["hello".to_string()]);
+}
+
+#[actix_web::test]
+async fn allow_sends_to_every_session() {
+    let broadcaster = BroadcasterHandle::new();
+    let (first, mut tab) = connect().await;
+    let (second, mut other) = connect().await;
+    let closed = second.clone();
+
+    broadcaster.write().unwrap().set_duplicate_policy(DuplicatePolicy::Allow);
+
+    broadcaster.handle("room", "a", first.clone()).unwrap();
+    broadcaster.handle("room", "a", second).unwrap();
+
+    let _ = broadcaster.broadcast("room", "both").await.unwrap();
+
+    assert_eq!(tab.texts().await, vec!["both".to_string()]);
+    assert_eq!(other.texts().await, vec!["both".to_string()]);
+
+    // closing one of the sessions keeps the connection with the other one.
+    broadcaster.close_session(None, "a", closed).await.unwrap();
+
+    let _ = broadcaster.broadcast("room", "one").await.unwrap();
+
+    assert_eq!(other.frames().await, vec![Frame::Close(None)]);
+    assert_eq!(tab.texts().await, vec!["one".to_string()]);
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), vec!["room".to_string()]);
+
+    // and the last one closes and removes it.
+    broadcaster.close_session(None, "a", first).await.unwrap();
+
+    assert_eq!(tab.frames().await, vec![Frame::Close(None)]);
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), Vec::<String>::new());
+}
@@ -0,0 +1,75 @@
+//! the events that the listeners of a broadcaster get.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use actix_wsb::{BroadcasterHandle, Event};
+use common::{connect, recorded};
+
+fn created(room: &str) -> Event {
+    Event::RoomCreated { room: room.to_string() }
+}
+
+fn removed(room: &str) -> Event {
+    Event::RoomRemoved { room: room.to_string() }
+}
+
+fn joined(room: &str, connection: &str) -> Event {
+    Event::Joined { room: room.to_string(), connection: connection.to_string() }
+}
+
+fn left(room: &str, connection: &str) -> Event {
+    Event::Left { room: room.to_string(), connection: connection.to_string() }
+}
+
+fn closed(room: &str, connection: &str) -> Event {
+    Event::Closed { room: room.to_string(), connection: connection.to_string() }
+}
+
+#[actix_web::test]
+async fn lifecycle_of_rooms_and_connections_is_emitted_in_order() {
+    let (broadcaster, events) = recorded();
+    let (a, _first) = connect().await;
+    let (b, _second) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+    broadcaster.join("other", "a").unwrap();
+    broadcaster.leave("other", "a").unwrap();
+    broadcaster.close_conn("room", None, "b").await.unwrap();
+    broadcaster.write().unwrap().remove_empty_rooms();
+
+    let closing = broadcaster.write().unwrap().remove_room("room");
+    let _ = closing.await;
+
+    assert_eq!(*events.lock().unwrap(), vec![
+        created("room"),
+        joined("room", "a"),
+        joined("room", "b"),
+        created("other"),
+        joined("other", "a"),
+        left("other", "a"),
+        closed("room", "b"),
+        removed("other"),
+        closed("room", "a"),
+        removed("room")
+    ]);
+}
+
+#[actix_web::test]
+async fn listeners_registered_later_hear_the_existing_rooms() {
+    let broadcaster = BroadcasterHandle::new();
+    let events = Arc::new(Mutex::new(vec![]));
+    let (a, _client) = connect().await;
+
+    broadcaster.write().unwrap().handle_room("room");
+
+    {
+        let events = Arc::clone(&events);
+        broadcaster.read().unwrap().on_event(move |event| events.lock().unwrap().push(event.clone()));
+    }
+
+    broadcaster.handle("room", "a", a).unwrap();
+
+    assert_eq!(*events.lock().unwrap(), vec![joined("room", "a")]);
+}
@@ -0,0 +1,60 @@
+//! the connection ids that the broadcaster generates.
+
+mod common;
+
+use std::time::Duration;
+use actix_web::rt::time::sleep;
+use actix_wsb::{BroadcasterHandle, IdGenerator, Metadata};
+use common::connect;
+use ulid::Ulid;
+use uuid::Uuid;
+
+#[actix_web::test]
+async fn uuids_are_generated_by_default() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _first) = connect().await;
+    let (b, _second) = connect().await;
+
+    let first = broadcaster.handle_generated("room", a).unwrap();
+    let second = broadcaster.handle_generated("room", b).unwrap();
+
+    assert_eq!(Uuid::parse_str(&first).unwrap().get_version_num(), 4);
+    assert_ne!(first, second);
+    assert_eq!(broadcaster.rooms_of(&second).unwrap(), vec!["room".to_string()]);
+}
+
+#[actix_web::test]
+async fn ulids_are_sorted_by_the_time_they_are_generated() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _first) = connect().await;
+    let (b, _second) = connect().await;
+
+    broadcaster.write().unwrap().set_id_generator(IdGenerator::Ulid);
+
+    let first = broadcaster.handle_generated("room", a).unwrap();
+    sleep(Duration::from_millis(2)).await;
+    let second = broadcaster.handle_generated("room", b).unwrap();
+
+    assert!(Ulid::from_string(&first).is_ok() && Ulid::from_string(&second).is_ok());
+    assert!(first < second);
+}
+
+#[actix_web::test]
+async fn counter_is_shared_by_the_copies_of_the_handle() {
+    let broadcaster = BroadcasterHandle::new();
+    let copy = broadcaster.clone();
+    let (a, mut client) = connect().await;
+    let (b, _second) = connect().await;
+
+    broadcaster.write().unwrap().set_id_generator(IdGenerator::Counter);
+
+    let first = broadcaster.handle_generated_with("room", a, Metadata::new().role("admin")).unwrap();
+    let second = copy.handle_generated("other", b).unwrap();
+
+    assert_eq!((first.as_str(), second.as_str()), ("1", "2"));
+
+    let report = broadcaster.broadcast_if("room", "admins", |connection| connection.metadata.has_role("admin")).await.unwrap();
+
+    assert_eq!(report.delivered, vec!["1".to_string()]);
+    assert_eq!(client.texts().await, vec!["admins".to_string()]);
+}
@@ -0,0 +1,39 @@
+//! the heartbeat supervisor, that closes the connections which don't answer it's pings.
+
+mod common;
+
+use std::time::Duration;
+use actix_web::rt::time::sleep;
+use actix_wsb::{Broadcaster, Event, HeartbeatConfig};
+use common::{connect, record, Frame};
+
+#[actix_web::test]
+async fn silent_connections_time_out() {
+    let broadcaster = Broadcaster::new();
+    let events = record(&broadcaster);
+    let (a, mut silent) = connect().await;
+    let (b, mut answering) = connect().await;
+
+    Broadcaster::handle(&broadcaster, "room", "a", a).unwrap();
+    Broadcaster::handle(&broadcaster, "room", "b", b).unwrap();
+
+    let supervisor = Broadcaster::heartbeat(&broadcaster, HeartbeatConfig { interval: Duration::from_millis(50), max_missed: 2, close_reason: None });
+
+    for _ in 0..20 {
+        sleep(Duration::from_millis(15)).await;
+
+        broadcaster.read().unwrap().record_pong("b", b"").unwrap();
+    }
+
+    supervisor.abort();
+
+    let frames = silent.frames().await;
+
+    assert_eq!(frames.iter().filter(|frame| matches!(frame, Frame::Ping(_))).count(), 2);
+    assert_eq!(frames.last(), Some(&Frame::Close(None)));
+    assert!(answering.frames().await.iter().all(|frame| matches!(frame, Frame::Ping(_))));
+
+    assert_eq!(broadcaster.read().unwrap().rooms_of("a"), Vec::<String>::new());
+    assert_eq!(broadcaster.read().unwrap().rooms_of("b"), vec!["room".to_string()]);
+    assert!(events.lock().unwrap().contains(&Event::TimedOut { room: "room".to_string(), connection: "a".to_string() }));
+}
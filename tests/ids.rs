@@ -0,0 +1,47 @@
+//! the rooms and connections whose ids are not strings, and the lookups by borrowed ids.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, Error, Room};
+use common::connect;
+
+#[actix_web::test]
+async fn numeric_ids_work_without_converting_them() {
+    let broadcaster = BroadcasterHandle::<(), u64, u64>::typed();
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    broadcaster.handle(&1, &10, a).unwrap();
+    broadcaster.handle(&1, &20, b).unwrap();
+    broadcaster.join(&2, &10).unwrap();
+
+    let report = broadcaster.broadcast_if(&1, "hi", |connection| connection.id == 20).await.unwrap();
+
+    assert_eq!((report.delivered, report.skipped), (vec![20], vec![10]));
+    assert_eq!(broadcaster.rooms_of(&20).unwrap(), vec![1]);
+    assert_eq!(broadcaster.broadcast(&2, "team").await.unwrap().delivered, vec![10]);
+    assert_eq!(first.texts().await, vec!["team".to_string()]);
+    assert_eq!(second.texts().await, vec!["hi".to_string()]);
+}
+
+#[actix_web::test]
+async fn errors_show_the_ids_that_are_not_found() {
+    let broadcaster = BroadcasterHandle::<(), u64, u64>::typed();
+
+    assert_eq!(broadcaster.broadcast(&3, "hi").await.err(), Some(Error::RoomNotFound("3".to_string())));
+    assert_eq!(broadcaster.join(&3, &30), Err(Error::ConnectionNotFound("30".to_string())));
+}
+
+#[actix_web::test]
+async fn string_ids_are_looked_up_by_str() {
+    let mut room = Room::create("room".to_string());
+    let (a, _client) = connect().await;
+    let id = String::from("a");
+
+    room.add_connection(id.as_str(), a).unwrap();
+
+    assert!(room.check_connection("a").is_some());
+    assert!(room.check_connection(&id).is_some());
+    assert_eq!(room.remove_connection("a"), Ok(()));
+    assert!(room.connectors.is_empty());
+}
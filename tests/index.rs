@@ -0,0 +1,84 @@
+//! the rooms by their id and the reverse index from the connections to their rooms.
+
+mod common;
+
+use actix_wsb::{Broadcaster, Error};
+use common::connect;
+
+/// the rooms of the connection in a stable order, the index doesn't keep one.
+fn rooms_of(broadcaster: &Broadcaster, id: &str) -> Vec<String> {
+    let mut rooms = broadcaster.rooms_of(id);
+    rooms.sort();
+
+    rooms
+}
+
+#[actix_web::test]
+async fn rooms_of_follows_the_joins_and_leaves() {
+    let broadcaster = Broadcaster::new();
+    let (a, _client) = connect().await;
+    let mut broadcaster = broadcaster.write().unwrap();
+
+    broadcaster.handle_room("personal").add_connection("a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    broadcaster.join("global", "a").unwrap();
+
+    assert_eq!(rooms_of(&broadcaster, "a"), vec!["global", "personal", "team"]);
+
+    broadcaster.leave("team", "a").unwrap();
+    broadcaster.room("global").unwrap().remove_connection("a").unwrap();
+
+    assert_eq!(rooms_of(&broadcaster, "a"), vec!["personal"]);
+    assert_eq!(rooms_of(&broadcaster, "missing"), Vec::<String>::new());
+}
+
+#[actix_web::test]
+async fn remove_connection_removes_every_membership() {
+    let broadcaster = Broadcaster::new();
+    let (a, _client) = connect().await;
+    let mut broadcaster = broadcaster.write().unwrap();
+
+    broadcaster.handle_room("personal").add_connection("a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+
+    assert!(broadcaster.remove_connection("a").is_ok());
+    assert_eq!(rooms_of(&broadcaster, "a"), Vec::<String>::new());
+    assert!(broadcaster.room("personal").unwrap().connectors.is_empty());
+    assert!(broadcaster.room("team").unwrap().connectors.is_empty());
+    assert_eq!(broadcaster.remove_connection("a").err(), Some(Error::ConnectionNotFound("a".to_string())));
+}
+
+#[actix_web::test]
+async fn removed_rooms_leave_the_index() {
+    let broadcaster = Broadcaster::new();
+    let (a, _client) = connect().await;
+
+    broadcaster.write().unwrap().handle_room("personal").add_connection("a", a).unwrap();
+    broadcaster.write().unwrap().join("team", "a").unwrap();
+
+    let closing = broadcaster.write().unwrap().remove_room("team");
+    let _ = closing.await;
+
+    assert_eq!(rooms_of(&broadcaster.read().unwrap(), "a"), vec!["personal"]);
+    assert!(!broadcaster.read().unwrap().check("team"));
+}
+
+#[actix_web::test]
+async fn rooms_and_connections_are_found_by_their_id() {
+    let broadcaster = Broadcaster::new();
+    let (a, _client) = connect().await;
+    let mut broadcaster = broadcaster.write().unwrap();
+
+    broadcaster.handle_room("room").add_connection("a", a).unwrap();
+
+    assert!(broadcaster.check("room"));
+    assert!(!broadcaster.check("missing"));
+    assert_eq!(broadcaster.room("missing").err(), Some(Error::RoomNotFound("missing".to_string())));
+    assert!(broadcaster.check_room("missing").is_none());
+
+    let room = broadcaster.room("room").unwrap();
+
+    assert_eq!(room.check_connection("a").map(|connection| connection.id), Some("a".to_string()));
+    assert!(room.check_connection("b").is_none());
+    assert_eq!(room.remove_connection("b"), Err(Error::ConnectionNotFound("b".to_string())));
+}
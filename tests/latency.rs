@@ -0,0 +1,106 @@
+//! the round-trip time measurements of the connections and the rooms.
+
+mod common;
+
+use std::time::Duration;
+use actix_web::rt::time::sleep;
+use actix_wsb::{Connection, Room};
+use common::{connect, Client, Frame};
+
+/// sends a timed ping from the connection and returns the payload that the client received.
+async fn ping(connection: &mut Connection, client: &mut Client) -> Vec<u8> {
+    connection.ping_timed().await.unwrap();
+
+    match client.frames().await.as_slice() {
+        [Frame::Ping(payload)] => payload.clone(),
+        frames => panic!("expected a single ping, got {frames:?}")
+    }
+}
+
+#[actix_web::test]
+async fn pong_of_a_timed_ping_records_the_round_trip_time() {
+    let (session, mut client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.add_connection("a", session).unwrap();
+
+    let mut connection = room.check_connection("a").unwrap();
+
+    assert_eq!(connection.rtt(), None);
+
+    let payload = ping(&mut connection, &mut client).await;
+
+    sleep(Duration::from_millis(20)).await;
+
+    assert!(!connection.record_pong(b"not timed"));
+    assert!(connection.record_pong(&payload));
+    // it's answered already.
+    assert!(!connection.record_pong(&payload));
+
+    let rtt = connection.rtt().unwrap();
+
+    assert!(rtt.last >= Duration::from_millis(20));
+    assert_eq!((rtt.min, rtt.avg, rtt.p99, rtt.samples), (rtt.last, rtt.last, rtt.last, 1));
+    assert_eq!(room.rtt(), Some(rtt));
+}
+
+#[actix_web::test]
+async fn answering_the_latest_ping_forgets_the_ones_before_it() {
+    let (session, mut client) = connect().await;
+    let mut connection = Connection::create("a".to_string(), session);
+
+    let first = ping(&mut connection, &mut client).await;
+    let second = ping(&mut connection, &mut client).await;
+
+    assert!(connection.record_pong(&second));
+    assert!(!connection.record_pong(&first));
+    assert_eq!(connection.rtt().unwrap().samples, 1);
+}
+
+#[actix_web::test]
+async fn statistics_cover_the_recent_measurements() {
+    let (session, mut client) = connect().await;
+    let mut connection = Connection::create("a".to_string(), session);
+
+    for wait in [10, 40] {
+        let payload = ping(&mut connection, &mut client).await;
+
+        sleep(Duration::from_millis(wait)).await;
+        connection.record_pong(&payload);
+    }
+
+    let rtt = connection.rtt().unwrap();
+
+    assert_eq!(rtt.samples, 2);
+    assert!(rtt.min >= Duration::from_millis(10) && rtt.min < rtt.last);
+    assert!(rtt.avg > rtt.min && rtt.avg < rtt.last);
+    assert_eq!(rtt.p99, rtt.last);
+}
+
+#[actix_web::test]
+async fn room_aggregates_the_measurements_of_the_connections() {
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.add_connection("a", a).unwrap();
+    room.add_connection("b", b).unwrap();
+
+    let mut slow = room.check_connection("a").unwrap();
+    let mut fast = room.check_connection("b").unwrap();
+
+    let slow_ping = ping(&mut slow, &mut first).await;
+    let fast_ping = ping(&mut fast, &mut second).await;
+
+    sleep(Duration::from_millis(10)).await;
+    fast.record_pong(&fast_ping);
+    sleep(Duration::from_millis(30)).await;
+    slow.record_pong(&slow_ping);
+
+    let rtt = room.rtt().unwrap();
+
+    assert_eq!(rtt.samples, 2);
+    assert_eq!(rtt.min, fast.rtt().unwrap().last);
+    // the slow one is answered after the fast one, so it's the latest.
+    assert_eq!(rtt.last, slow.rtt().unwrap().last);
+}
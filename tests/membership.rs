@@ -0,0 +1,66 @@
+//! the memberships of a connection in many rooms.
+
+mod common;
+
+use std::time::{Duration, Instant};
+use actix_wsb::{Error, Event};
+use common::{connect, numbered, recorded, settle};
+
+#[actix_web::test]
+async fn connection_closed_in_one_room_leaves_the_others() {
+    let (broadcaster, events) = recorded();
+    let (a, _client) = connect().await;
+
+    broadcaster.handle("personal", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+
+    broadcaster.close_conn("personal", None, "a").await.unwrap();
+    settle().await;
+
+    let report = broadcaster.broadcast("team", "hello").await.unwrap();
+
+    assert_eq!(report.pruned, vec!["a".to_string()]);
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), Vec::<String>::new());
+    assert!(events.lock().unwrap().contains(&Event::Pruned { room: "team".to_string(), connection: "a".to_string() }));
+}
+
+#[actix_web::test]
+async fn memberships_share_the_queue() {
+    let (broadcaster, _) = recorded();
+    let (a, mut client) = connect().await;
+
+    broadcaster.handle("personal", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+
+    for room in ["personal", "team", "personal"] {
+        let _ = broadcaster.broadcast(room, room).await.unwrap();
+    }
+
+    assert_eq!(client.texts().await, vec!["personal".to_string(), "team".to_string(), "personal".to_string()]);
+}
+
+#[actix_web::test]
+async fn disconnect_removes_every_membership_before_the_lock_is_released() {
+    let (broadcaster, events) = recorded();
+    let (a, _stalled) = connect().await;
+
+    broadcaster.handle("personal", "a", a).unwrap();
+    broadcaster.join("team", "a").unwrap();
+
+    // more than the session can buffer, so the writer task is stuck until the client reads.
+    for message in numbered(40) {
+        let _ = broadcaster.broadcast("team", message).await;
+    }
+
+    settle().await;
+
+    let closing = broadcaster.write().unwrap().disconnect(None, "a");
+
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), Vec::<String>::new());
+    assert_eq!(events.lock().unwrap().iter().filter(|event| matches!(event, Event::Closed { .. })).count(), 2);
+
+    let started = Instant::now();
+
+    assert_eq!(closing.await, Err(Error::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(6));
+}
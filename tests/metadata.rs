@@ -0,0 +1,57 @@
+//! the metadata of the connections and the conditions that read it.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, Error, Metadata};
+use common::connect;
+
+#[actix_web::test]
+async fn conditions_read_the_metadata_given_at_handle_time() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut admin) = connect().await;
+    let (b, mut member) = connect().await;
+
+    broadcaster.handle_with("room", "a", a, Metadata::new().user_id("1").role("admin").locale("tr-TR")).unwrap();
+    broadcaster.handle_with("room", "b", b, Metadata::new().user_id("2").tag("team", "backend")).unwrap();
+
+    let admins = broadcaster.broadcast_if("room", "admins", |connection| connection.metadata.has_role("admin")).await.unwrap();
+    let others = broadcaster.broadcast_if_not("room", "others", |connection| connection.metadata.locale.as_deref() == Some("tr-TR")).await.unwrap();
+    let backend = broadcaster.broadcast_if("room", "backend", |connection| connection.metadata.get("team") == Some("backend")).await.unwrap();
+
+    assert_eq!((admins.delivered, admins.skipped), (vec!["a".to_string()], vec!["b".to_string()]));
+    assert_eq!(others.delivered, vec!["b".to_string()]);
+    assert_eq!(backend.delivered, vec!["b".to_string()]);
+    assert_eq!(admin.texts().await, vec!["admins".to_string()]);
+    assert_eq!(member.texts().await, vec!["others".to_string(), "backend".to_string()]);
+}
+
+#[actix_web::test]
+async fn updated_metadata_is_seen_in_every_room() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut client) = connect().await;
+
+    broadcaster.handle_with("personal", "a", a, Metadata::new().user_id("1")).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    broadcaster.update_metadata("a", |metadata| { metadata.roles.insert("moderator".to_string()); }).unwrap();
+
+    for room in ["personal", "team"] {
+        let report = broadcaster.broadcast_if(room, room, |connection| connection.metadata.has_role("moderator") && connection.metadata.user_id.as_deref() == Some("1")).await.unwrap();
+
+        assert_eq!(report.delivered, vec!["a".to_string()]);
+    }
+
+    assert_eq!(client.texts().await, vec!["personal".to_string(), "team".to_string()]);
+    assert_eq!(broadcaster.update_metadata("b", |_| ()), Err(Error::ConnectionNotFound("b".to_string())));
+}
+
+#[actix_web::test]
+async fn connections_without_metadata_get_an_empty_one() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _client) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+
+    let connection = broadcaster.write().unwrap().room("room").unwrap().check_connection("a").unwrap();
+
+    assert_eq!(connection.metadata, Metadata::new());
+}
@@ -0,0 +1,45 @@
+//! moving a connection between rooms.
+
+mod common;
+
+use actix_wsb::{Error, Event};
+use common::{connect, recorded};
+
+#[actix_web::test]
+async fn move_keeps_the_messages_of_both_rooms_in_order() {
+    let (broadcaster, events) = recorded();
+    let (a, mut client) = connect().await;
+    let (b, _other) = connect().await;
+
+    broadcaster.handle("lobby", "a", a).unwrap();
+    broadcaster.handle("game", "b", b).unwrap();
+
+    let _ = broadcaster.broadcast("lobby", "lobby before").await.unwrap();
+    let _ = broadcaster.broadcast("game", "game before").await.unwrap();
+    broadcaster.move_connection("a", "lobby", "game").unwrap();
+    let _ = broadcaster.broadcast("lobby", "lobby after").await.unwrap();
+    let _ = broadcaster.broadcast("game", "game after").await.unwrap();
+
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), vec!["game".to_string()]);
+
+    // the message that queued before the move is still sent, and only the messages of the target room after it.
+    assert_eq!(client.texts().await, vec!["lobby before".to_string(), "game after".to_string()]);
+
+    let events = events.lock().unwrap();
+    let left = events.iter().position(|event| *event == Event::Left { room: "lobby".to_string(), connection: "a".to_string() });
+    let joined = events.iter().position(|event| *event == Event::Joined { room: "game".to_string(), connection: "a".to_string() });
+
+    assert!(left.is_some() && left < joined);
+}
+
+#[actix_web::test]
+async fn failed_move_doesnt_change_the_rooms() {
+    let (broadcaster, _) = recorded();
+    let (a, _client) = connect().await;
+
+    broadcaster.handle("lobby", "a", a).unwrap();
+
+    assert_eq!(broadcaster.move_connection("a", "missing", "game"), Err(Error::RoomNotFound("missing".to_string())));
+    assert_eq!(broadcaster.move_connection("b", "lobby", "game"), Err(Error::ConnectionNotFound("b".to_string())));
+    assert_eq!(broadcaster.rooms_of("a").unwrap(), vec!["lobby".to_string()]);
+}
@@ -0,0 +1,61 @@
+//! the payloads that are shared by every connection of a room instead of copied for each of them.
+
+mod common;
+
+use actix_web::web::Bytes;
+use actix_wsb::{ByteString, Room};
+use common::{connect, Frame};
+
+#[actix_web::test]
+async fn shared_text_reaches_every_connection() {
+    let mut room = Room::create("room".to_string());
+    let mut clients = vec![];
+
+    for id in ["a", "b", "c"] {
+        let (session, client) = connect().await;
+
+        room.add_connection(id, session).unwrap();
+        clients.push(client);
+    }
+
+    // larger than a frame with a 16 bit length, like the JSON payloads that the shared buffer is for.
+    let payload = ByteString::from("x".repeat(100 * 1024));
+    let report = room.broadcast(payload.clone()).await;
+
+    assert_eq!(report.delivered.len(), 3);
+
+    for client in clients.iter_mut() {
+        assert_eq!(client.texts().await, vec![payload.to_string()]);
+    }
+}
+
+#[actix_web::test]
+async fn shared_binary_reaches_the_matching_connections() {
+    let mut room = Room::create("room".to_string());
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    room.add_connection("a", a).unwrap();
+    room.add_connection("b", b).unwrap();
+
+    let payload = Bytes::from_static(&[0, 1, 2, 255]);
+    let report = room.binary_if(payload.clone(), |connection| connection.id == "a").await;
+
+    assert_eq!((report.delivered, report.skipped), (vec!["a".to_string()], vec!["b".to_string()]));
+    assert_eq!(first.frames().await, vec![Frame::Binary(payload.to_vec())]);
+    assert!(second.frames().await.is_empty());
+}
+
+#[actix_web::test]
+async fn owned_and_borrowed_strings_are_sent_too() {
+    let mut room = Room::create("room".to_string());
+    let (a, mut client) = connect().await;
+
+    room.add_connection("a", a).unwrap();
+
+    let _ = room.broadcast("borrowed").await;
+    let _ = room.broadcast(String::from("owned")).await;
+    room.check_connection("a").unwrap().send(ByteString::from_static("direct")).await.unwrap();
+
+    assert_eq!(client.texts().await, vec!["borrowed".to_string(), "owned".to_string(), "direct".to_string()]);
+}
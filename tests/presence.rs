@@ -0,0 +1,126 @@
+//! the presence messages of the rooms.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, DuplicatePolicy};
+use common::{connect, numbered, settle};
+
+const EMPTY: &str = r#"{"user_id":null,"roles":[],"locale":null,"tags":{}}"#;
+
+#[actix_web::test]
+async fn joins_and_leaves_are_announced() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut first) = connect().await;
+    let (b, mut second) = connect().await;
+
+    broadcaster.write().unwrap().set_presence(true);
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    assert_eq!(first.texts().await, vec![
+        format!(r#"{{"type":"presence_state","members":[{{"id":"a","metadata":{EMPTY}}}]}}"#),
+        format!(r#"{{"type":"presence_join","id":"b","metadata":{EMPTY}}}"#)
+    ]);
+
+    let state = second.texts().await;
+
+    assert_eq!(state.len(), 1);
+    assert!(state[0].starts_with(r#"{"type":"presence_state","members":["#));
+    assert!(state[0].contains(&format!(r#"{{"id":"a","metadata":{EMPTY}}}"#)));
+    assert!(state[0].contains(&format!(r#"{{"id":"b","metadata":{EMPTY}}}"#)));
+
+    broadcaster.disconnect(None, "b").await.unwrap();
+
+    assert_eq!(first.texts().await, vec![r#"{"type":"presence_leave","id":"b"}"#.to_string()]);
+}
+
+#[actix_web::test]
+async fn presence_messages_have_reserved_slots() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut first) = connect().await;
+    let (b, _second) = connect().await;
+
+    broadcaster.write().unwrap().set_presence(true);
+    broadcaster.write().unwrap().set_queue_capacity(1);
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    // the queue of "a" has it's presence list already, so the broadcast is dropped but the join isn't.
+    let _ = broadcaster.broadcast("room", "dropped").await.unwrap();
+
+    let texts = first.texts().await;
+
+    assert_eq!(texts.len(), 2);
+    assert!(texts[1].starts_with(r#"{"type":"presence_join","id":"b""#));
+}
+
+#[actix_web::test]
+async fn presence_messages_beyond_the_reserved_slots_are_dropped() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, _stalled) = connect().await;
+    let mut others = vec![];
+
+    broadcaster.write().unwrap().set_presence(true);
+    broadcaster.write().unwrap().set_queue_capacity(1);
+
+    broadcaster.handle("room", "a", a).unwrap();
+
+    // more than the session can buffer, so the writer task of "a" is stuck and it's queue is full.
+    for message in numbered(40) {
+        let _ = broadcaster.broadcast("room", message).await;
+    }
+
+    settle().await;
+
+    for i in 0..20 {
+        let (session, client) = connect().await;
+
+        broadcaster.handle("room", &i.to_string(), session).unwrap();
+        others.push(client);
+    }
+
+    let connection = broadcaster.write().unwrap().room("room").unwrap().check_connection("a").unwrap();
+
+    // one broadcast and eight joins.
+    assert_eq!(connection.queued(), 9);
+}
+
+#[actix_web::test]
+async fn enabling_presence_sends_the_list_to_the_connections() {
+    let broadcaster = BroadcasterHandle::new();
+    let (a, mut first) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.write().unwrap().set_presence(true);
+    broadcaster.write().unwrap().set_presence(true);
+
+    assert_eq!(first.texts().await, vec![
+        format!(r#"{{"type":"presence_state","members":[{{"id":"a","metadata":{EMPTY}}}]}}"#)
+    ]);
+}
+
+#[actix_web::test]
+async fn attached_sessions_get_the_presence_list() {
+    let broadcaster = BroadcasterHandle::new();
+    let (first, _tab) = connect().await;
+    let (second, mut other) = connect().await;
+
+    broadcaster.write().unwrap().set_presence(true);
+    broadcaster.write().unwrap().set_duplicate_policy(DuplicatePolicy::Allow);
+
+    broadcaster.handle("room", "a", first).unwrap();
+
+    // the other tab connects after the presence list of the first one is written.
+    settle().await;
+
+    broadcaster.handle("room", "a", second).unwrap();
+
+    let _ = broadcaster.broadcast("room", "hello").await.unwrap();
+
+    assert_eq!(other.texts().await, vec![
+        format!(r#"{{"type":"presence_state","members":[{{"id":"a","metadata":{EMPTY}}}]}}"#),
+        "hello".to_string()
+    ]);
+}
@@ -0,0 +1,29 @@
+//! pruning the connections whose session is closed from their rooms.
+
+mod common;
+
+use actix_wsb::Event;
+use common::{connect, recorded, settle};
+
+#[actix_web::test]
+async fn closed_sessions_are_pruned() {
+    let (broadcaster, events) = recorded();
+    let (a, mut first) = connect().await;
+    let (b, second) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle("room", "b", b).unwrap();
+
+    // the client of "b" is gone, so it's writer task fails and stops.
+    drop(second);
+    let _ = broadcaster.broadcast("room", "first").await.unwrap();
+    settle().await;
+
+    let report = broadcaster.broadcast("room", "second").await.unwrap();
+
+    assert_eq!(report.delivered, vec!["a".to_string()]);
+    assert_eq!(report.pruned, vec!["b".to_string()]);
+    assert_eq!(broadcaster.rooms_of("b").unwrap(), Vec::<String>::new());
+    assert!(events.lock().unwrap().contains(&Event::Pruned { room: "room".to_string(), connection: "b".to_string() }));
+    assert_eq!(first.texts().await, vec!["first".to_string(), "second".to_string()]);
+}
@@ -0,0 +1,103 @@
+//! the outbound queue of a connection: the order of the messages, closing after the queued ones and dropping the last clone.
+
+mod common;
+
+use std::time::Duration;
+use actix_ws::{CloseCode, CloseReason};
+use actix_wsb::{Connection, Error, Room};
+use common::{connect, numbered, settle, Frame};
+
+#[actix_web::test]
+async fn messages_are_sent_in_the_order_they_are_queued() {
+    let (session, mut client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.add_connection("a", session).unwrap();
+
+    for message in numbered(20) {
+        let report = room.broadcast(message).await;
+
+        assert_eq!(report.delivered, vec!["a".to_string()]);
+    }
+
+    assert_eq!(client.texts().await, numbered(20));
+}
+
+#[actix_web::test]
+async fn close_is_sent_after_the_queued_messages() {
+    let (session, mut client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.add_connection("a", session).unwrap();
+
+    for message in numbered(3) {
+        let _ = room.broadcast(message).await;
+    }
+
+    let reason = CloseReason { code: CloseCode::Normal, description: None };
+
+    room.close_conn(Some(reason), "a").await.unwrap();
+
+    let frames = client.frames().await;
+
+    assert_eq!(frames, vec![Frame::Text("m0".into()), Frame::Text("m1".into()), Frame::Text("m2".into()), Frame::Close(Some(1000))]);
+    assert!(room.connectors.is_empty());
+    assert_eq!(room.close_conn(None, "a").await, Err(actix_wsb::Error::ConnectionNotFound("a".to_string())));
+}
+
+#[actix_web::test]
+async fn dropped_connection_sends_the_queued_messages_and_stops() {
+    let (session, mut client) = connect().await;
+    let mut connection = Connection::create("a".to_string(), session);
+
+    for message in numbered(5) {
+        connection.send(message).await.unwrap();
+    }
+
+    assert_eq!(connection.queued(), 5);
+
+    drop(connection);
+
+    assert_eq!(client.texts().await, numbered(5));
+    assert!(client.ended());
+}
+
+#[actix_web::test]
+async fn clones_keep_the_queue_open() {
+    let (session, mut client) = connect().await;
+    let mut connection = Connection::create("a".to_string(), session);
+    let mut clone = connection.clone();
+
+    connection.send("first").await.unwrap();
+    drop(connection);
+    clone.send("second").await.unwrap();
+
+    assert_eq!(client.texts().await, vec!["first".to_string(), "second".to_string()]);
+    assert!(!client.ended());
+}
+
+#[actix_web::test]
+async fn close_gives_up_on_a_client_that_stops_reading() {
+    let (session, mut client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.add_connection("a", session).unwrap();
+
+    // more than the session can buffer, so the writer task is stuck until the client reads.
+    for message in numbered(40) {
+        let _ = room.broadcast(message).await;
+    }
+
+    settle().await;
+
+    let started = std::time::Instant::now();
+
+    assert_eq!(room.close_conn(None, "a").await, Err(Error::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(6));
+
+    let frames = client.frames().await;
+
+    assert!(client.ended());
+    assert!(frames.len() < 40);
+    assert!(!frames.iter().any(|frame| matches!(frame, Frame::Close(_))));
+}
@@ -0,0 +1,140 @@
+//! the shards of `ShardedBroadcaster` and the locks of them.
+
+mod common;
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use actix_wsb::{Error, Event, ShardedBroadcaster};
+use common::connect;
+
+/// runs given function on another thread and returns true if it doesn't return in a while, because it waits for a lock that's held by the caller. The thread finishes after the lock is released.
+fn blocks<F>(f: F) -> bool where F: FnOnce() + Send + 'static {
+    let (done, finished) = mpsc::channel();
+
+    thread::spawn(move || {
+        f();
+        let _ = done.send(());
+    });
+
+    finished.recv_timeout(Duration::from_millis(100)).is_err()
+}
+
+/// finds a room id whose shard is not the one of given room.
+fn elsewhere(broadcaster: &ShardedBroadcaster, room_id: &str) -> String {
+    let guard = broadcaster.handle_room(room_id).unwrap();
+
+    let found = (0..64).map(|i| format!("room-{i}")).find(|candidate| {
+        let (broadcaster, candidate) = (broadcaster.clone(), candidate.clone());
+
+        !blocks(move || { broadcaster.check(&candidate); })
+    });
+
+    drop(guard);
+
+    found.expect("every room is in the same shard")
+}
+
+#[actix_web::test]
+async fn a_room_locks_only_the_shard_it_is_in() {
+    let broadcaster = ShardedBroadcaster::with_shards(16);
+    let other = elsewhere(&broadcaster, "room");
+
+    let guard = broadcaster.room("room").unwrap();
+    let same = broadcaster.clone();
+    let different = broadcaster.clone();
+
+    assert!(blocks(move || { same.check("room"); }));
+    assert!(!blocks(move || { different.check(&other); }));
+
+    drop(guard);
+}
+
+#[actix_web::test]
+async fn a_single_shard_holds_every_room() {
+    let broadcaster = ShardedBroadcaster::with_shards(1);
+    let guard = broadcaster.handle_room("first").unwrap();
+    let locked = broadcaster.clone();
+
+    assert!(blocks(move || { locked.check("second"); }));
+
+    drop(guard);
+}
+
+#[actix_web::test]
+async fn room_guard_derefs_to_the_room() {
+    let broadcaster = ShardedBroadcaster::new();
+    let (a, mut client) = connect().await;
+
+    assert_eq!(broadcaster.room("room").err(), Some(Error::RoomNotFound("room".to_string())));
+    assert!(broadcaster.check_room("room").is_none());
+
+    broadcaster.handle_room("room").unwrap().add_connection("a", a).unwrap();
+
+    assert_eq!(broadcaster.room("room").unwrap().connectors.len(), 1);
+    assert_eq!(broadcaster.rooms_of("a"), vec!["room".to_string()]);
+
+    let _ = broadcaster.broadcast("room", "hello").await.unwrap();
+
+    assert_eq!(client.texts().await, vec!["hello".to_string()]);
+}
+
+#[actix_web::test]
+async fn move_connection_between_two_shards() {
+    let broadcaster = ShardedBroadcaster::with_shards(16);
+    let events = Arc::new(Mutex::new(vec![]));
+    let target = elsewhere(&broadcaster, "source");
+    let (a, mut client) = connect().await;
+
+    {
+        let events = Arc::clone(&events);
+        broadcaster.on_event(move |event| events.lock().unwrap().push(event.clone()));
+    }
+
+    broadcaster.handle("source", "a", a).unwrap();
+    broadcaster.move_connection("a", "source", &target).unwrap();
+
+    assert_eq!(broadcaster.rooms_of("a"), vec![target.clone()]);
+    assert!(broadcaster.room("source").unwrap().connectors.is_empty());
+    assert!(events.lock().unwrap().contains(&Event::RoomCreated { room: target.clone() }));
+
+    let _ = broadcaster.broadcast(&target, "moved").await.unwrap();
+
+    assert_eq!(client.texts().await, vec!["moved".to_string()]);
+}
+
+/// holds the lock of the shard of `to` while a move from `from` waits for it, and returns true if the move locked the shard of `from` meanwhile.
+fn locks_the_source_before(broadcaster: &ShardedBroadcaster, conn_id: &'static str, from: &str, to: &str) -> bool {
+    let guard = broadcaster.room(to).unwrap();
+    let (mover, probe) = (broadcaster.clone(), broadcaster.clone());
+    let (source, target) = (from.to_string(), to.to_string());
+
+    assert!(blocks(move || mover.move_connection(conn_id, &source, &target).unwrap()));
+
+    let source = from.to_string();
+    let locked = blocks(move || { probe.check(&source); });
+
+    drop(guard);
+
+    while broadcaster.rooms_of(conn_id) != vec![to.to_string()] {
+        thread::yield_now();
+    }
+
+    locked
+}
+
+#[actix_web::test]
+async fn moves_lock_the_shards_in_the_same_order() {
+    let broadcaster = ShardedBroadcaster::with_shards(16);
+    let other = elsewhere(&broadcaster, "room");
+    let (a, _client) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+    broadcaster.handle_room(&other).unwrap();
+
+    // a move locks the shard with the lower position first whichever room it's from, so two moves in opposite directions can't wait for each other forever. One of the moves has to wait for the target before locking the source.
+    let forth = locks_the_source_before(&broadcaster, "a", "room", &other);
+    let back = locks_the_source_before(&broadcaster, "a", &other, "room");
+
+    assert_ne!(forth, back);
+}
@@ -0,0 +1,166 @@
+//! the slow consumer policies. The writer task of a connection doesn't run until the test awaits something that yields, so the sends in a row fill the queue deterministically.
+
+mod common;
+
+use std::time::{Duration, Instant};
+use actix_web::web::Bytes;
+use actix_ws::{CloseCode, CloseReason, Item};
+use actix_wsb::{Error, Room, SlowConsumerPolicy};
+use common::{connect, numbered, settle, Client, Frame};
+
+/// a room with a single connection "a" whose queue holds two messages.
+async fn room(policy: SlowConsumerPolicy) -> (Room, Client) {
+    let (session, client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.set_queue_capacity(2);
+    room.set_slow_consumer_policy(policy);
+    room.add_connection("a", session).unwrap();
+
+    (room, client)
+}
+
+#[actix_web::test]
+async fn drop_newest_keeps_the_queued_messages() {
+    let (mut room, mut client) = room(SlowConsumerPolicy::DropNewest).await;
+    let mut failed = vec![];
+
+    for message in numbered(5) {
+        failed.extend(room.broadcast(message).await.failed);
+    }
+
+    assert_eq!(failed, vec![("a".to_string(), Error::QueueFull); 3]);
+    assert_eq!(room.connectors["a"].dropped(), 3);
+    assert_eq!(client.texts().await, numbered(2));
+}
+
+#[actix_web::test]
+async fn drop_oldest_keeps_the_newest_messages() {
+    let (mut room, mut client) = room(SlowConsumerPolicy::DropOldest).await;
+
+    for message in numbered(5) {
+        let report = room.broadcast(message).await;
+
+        assert!(report.failed.is_empty());
+    }
+
+    assert_eq!(room.connectors["a"].dropped(), 3);
+    assert_eq!(client.texts().await, vec!["m3".to_string(), "m4".to_string()]);
+}
+
+#[actix_web::test]
+async fn drop_oldest_drops_a_fragmented_message_as_a_whole() {
+    let (mut room, mut client) = room(SlowConsumerPolicy::DropOldest).await;
+
+    room.set_queue_capacity(3);
+
+    let _ = room.continuation(Item::FirstText(Bytes::from("first"))).await;
+    let _ = room.continuation(Item::Last(Bytes::from("last"))).await;
+    let _ = room.broadcast("m0").await;
+    let report = room.broadcast("m1").await;
+
+    assert!(report.failed.is_empty());
+    assert_eq!(client.frames().await, vec![Frame::Text("m0".to_string()), Frame::Text("m1".to_string())]);
+}
+
+#[actix_web::test]
+async fn drop_oldest_keeps_the_rest_of_a_partly_sent_message() {
+    let (mut room, mut client) = room(SlowConsumerPolicy::DropOldest).await;
+
+    let _ = room.continuation(Item::FirstText(Bytes::from("first"))).await;
+    settle().await;
+
+    let _ = room.continuation(Item::Continue(Bytes::from("middle"))).await;
+    let _ = room.continuation(Item::Last(Bytes::from("last"))).await;
+
+    // nothing in the queue can be dropped, so the new message is dropped instead.
+    let report = room.broadcast("m0").await;
+
+    assert_eq!(report.failed, vec![("a".to_string(), Error::QueueFull)]);
+    assert_eq!(client.frames().await, vec![Frame::Text("first".to_string()), Frame::Other(0), Frame::Other(0)]);
+}
+
+#[actix_web::test]
+async fn drop_oldest_keeps_the_presence_messages() {
+    let (session, mut client) = connect().await;
+    let mut room = Room::create("room".to_string());
+
+    room.set_queue_capacity(1);
+    room.set_slow_consumer_policy(SlowConsumerPolicy::DropOldest);
+    room.set_presence(true);
+    room.add_connection("a", session).unwrap();
+
+    let report = room.broadcast("m0").await;
+    let texts = client.texts().await;
+
+    assert_eq!(report.failed, vec![("a".to_string(), Error::QueueFull)]);
+    assert_eq!(texts.len(), 1);
+    assert!(texts[0].starts_with(r#"{"type":"presence_state""#));
+}
+
+#[actix_web::test]
+async fn disconnect_closes_and_prunes_the_connection() {
+    let reason = CloseReason { code: CloseCode::Again, description: None };
+    let (mut room, mut client) = room(SlowConsumerPolicy::Disconnect(Some(reason))).await;
+
+    let _ = room.broadcast("m0").await;
+    let _ = room.broadcast("m1").await;
+    let report = room.broadcast("m2").await;
+
+    assert_eq!(report.failed, vec![("a".to_string(), Error::Disconnected)]);
+    assert_eq!(report.pruned, vec!["a".to_string()]);
+    assert!(room.connectors.is_empty());
+    // the queued messages are discarded, only the close frame is sent.
+    assert_eq!(client.frames().await, vec![Frame::Close(Some(1013))]);
+}
+
+#[actix_web::test]
+async fn block_times_out_when_the_queue_stays_full() {
+    let (mut room, _client) = room(SlowConsumerPolicy::Block(Duration::from_millis(50))).await;
+
+    // the client doesn't read, so the writer task gets stuck when the buffer of the session is full and the queue stays full after that.
+    for message in numbered(64) {
+        let started = Instant::now();
+        let report = room.broadcast(message).await;
+
+        if report.failed == vec![("a".to_string(), Error::Timeout)] {
+            assert!(started.elapsed() >= Duration::from_millis(50));
+            assert!(room.connectors.contains_key("a"));
+
+            return;
+        }
+
+        assert_eq!(report.delivered, vec!["a".to_string()]);
+    }
+
+    panic!("no send timed out");
+}
+
+#[actix_web::test]
+async fn block_wakes_up_when_the_queue_has_room() {
+    let (mut room, mut client) = room(SlowConsumerPolicy::Block(Duration::from_secs(10))).await;
+    let mut sent = 0;
+
+    // fills the buffer of the session and the queue, so the next send has to wait for the client.
+    loop {
+        let message = format!("m{sent}");
+        let blocked = actix_web::rt::time::timeout(Duration::from_millis(50), room.broadcast(message)).await.is_err();
+
+        sent += 1;
+
+        if blocked {
+            break;
+        }
+    }
+
+    let started = Instant::now();
+    let (report, texts) = futures_util::future::join(room.broadcast("last"), async {
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+
+        client.texts().await
+    }).await;
+
+    assert_eq!(report.delivered, vec!["a".to_string()]);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(texts.last().map(String::as_str), Some("last"));
+}
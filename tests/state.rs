@@ -0,0 +1,66 @@
+//! the typed state of the connections and the conditions that read it.
+
+mod common;
+
+use actix_wsb::{BroadcasterHandle, Error};
+use common::connect;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct User {
+    id: u64,
+    admin: bool
+}
+
+#[actix_web::test]
+async fn conditions_read_the_state_given_at_handle_time() {
+    let broadcaster = BroadcasterHandle::<User>::typed();
+    let (a, mut admin) = connect().await;
+    let (b, mut member) = connect().await;
+
+    broadcaster.handle_with_state("room", "a", a, User { id: 1, admin: true }).unwrap();
+    broadcaster.handle_with_state("room", "b", b, User { id: 2, admin: false }).unwrap();
+
+    let admins = broadcaster.broadcast_if("room", "admins", |connection| connection.state.admin).await.unwrap();
+    let others = broadcaster.broadcast_if_not("room", "not 1", |connection| connection.state.id == 1).await.unwrap();
+
+    assert_eq!((admins.delivered, admins.skipped), (vec!["a".to_string()], vec!["b".to_string()]));
+    assert_eq!(others.delivered, vec!["b".to_string()]);
+    assert_eq!(admin.texts().await, vec!["admins".to_string()]);
+    assert_eq!(member.texts().await, vec!["not 1".to_string()]);
+}
+
+#[actix_web::test]
+async fn updated_state_is_seen_in_every_room() {
+    let broadcaster = BroadcasterHandle::<User>::typed();
+    let (a, mut client) = connect().await;
+
+    broadcaster.handle_with_state("personal", "a", a, User { id: 1, admin: false }).unwrap();
+    broadcaster.join("team", "a").unwrap();
+    broadcaster.write().unwrap().update_state("a", |user| user.admin = true).unwrap();
+
+    for room in ["personal", "team"] {
+        let report = broadcaster.broadcast_if(room, room, |connection| connection.state == User { id: 1, admin: true }).await.unwrap();
+
+        assert_eq!(report.delivered, vec!["a".to_string()]);
+    }
+
+    assert_eq!(client.texts().await, vec!["personal".to_string(), "team".to_string()]);
+    assert_eq!(broadcaster.write().unwrap().update_state("b", |_| ()), Err(Error::ConnectionNotFound("b".to_string())));
+}
+
+#[actix_web::test]
+async fn connections_without_state_get_the_default_one() {
+    let broadcaster = BroadcasterHandle::<User>::typed();
+    let (a, mut client) = connect().await;
+
+    broadcaster.handle("room", "a", a).unwrap();
+
+    let mut connection = broadcaster.write().unwrap().room("room").unwrap().check_connection("a").unwrap();
+
+    assert_eq!(connection.state, User::default());
+
+    connection.send_if("default", |connection| connection.state.id == 0).await.unwrap();
+    connection.send_if_not("skipped", |connection| connection.state.id == 0).await.unwrap();
+
+    assert_eq!(client.texts().await, vec!["default".to_string()]);
+}